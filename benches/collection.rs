@@ -0,0 +1,112 @@
+// Timings for Collection and for dispatching entity actions, run with cargo bench. Plain
+// Instant timings rather than a harness, so they're rough: compare runs on the same machine.
+//
+// What to expect as sizes grow from 100 to 100k: len is O(1), the count kept by the ids vector;
+// get, add and remove are O(log n), remove finding an id's place by binary search over the keys
+// ordering the ids, though splitting the ids around it and joining them again makes it the slowest
+// of the three; iter is O(n). Cloning a collection, as every reducer does, is O(1).
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use rs_redux::{entity_reducer, Collection, EntityAction, Identifiable, Store};
+
+#[derive(Clone, Debug)]
+struct Item {
+    id: i32,
+    value: u64,
+}
+
+impl Identifiable for Item {
+    type Id = i32;
+
+    fn get_id(&self) -> i32 {
+        self.id
+    }
+}
+
+fn items(n: usize) -> Vec<Item> {
+    (0..n as i32).map(|id| Item { id, value: id as u64 }).collect()
+}
+
+fn filled(n: usize) -> Collection<Item> {
+    Collection::new().add_many(&items(n))
+}
+
+// runs f once per iteration, printing the mean time it took
+fn time(name: &str, iterations: usize, mut f: impl FnMut(usize)) -> Duration {
+
+    let start = Instant::now();
+    for i in 0..iterations {
+        f(i);
+    }
+    let per_iteration = start.elapsed() / iterations as u32;
+
+    println!("{:<48} {:>12?}", name, per_iteration);
+    per_iteration
+}
+
+fn collection(n: usize) {
+
+    let collection = filled(n);
+    let iterations = 1000;
+
+    time(&format!("len of {}", n), iterations, |_| {
+        black_box(collection.len());
+    });
+
+    time(&format!("iter over {}", n), 10, |_| {
+        black_box(collection.iter().map(|item| item.value).sum::<u64>());
+    });
+
+    time(&format!("get from {}", n), iterations, |i| {
+        black_box(collection.get(&((i % n) as i32)));
+    });
+
+    time(&format!("add to {}", n), iterations, |i| {
+        black_box(collection.clone().add(&Item { id: (n + i) as i32, value: 0 }));
+    });
+
+    time(&format!("remove from the middle of {}", n), iterations, |_| {
+        black_box(collection.clone().remove(&((n / 2) as i32)));
+    });
+}
+
+fn store(collection: Collection<Item>) -> Store<Collection<Item>, EntityAction<Item>> {
+    let mut store = Store::new(collection);
+    store.register_reducer(Box::new(entity_reducer));
+    store
+}
+
+// each added in a dispatch of its own
+fn dispatch_adds(collection: Collection<Item>, items: &[Item]) {
+    let mut store = store(collection);
+    for item in items {
+        store.dispatch(EntityAction::AddEntity(item.clone())).expect("entity_reducer can't fail");
+    }
+    black_box(store.get_state());
+}
+
+fn main() {
+
+    for &n in &[100, 10_000, 100_000] {
+        collection(n);
+    }
+
+    let sequential = items(10_000);
+
+    time("dispatching 10k AddEntity, hashed", 5, |_| dispatch_adds(Collection::new(), &sequential));
+    time("dispatching 10k AddEntity, dense", 5, |_| dispatch_adds(Collection::new_dense(), &sequential));
+
+    time("dispatching AddMany of 10k", 5, |_| {
+        let mut store = store(Collection::new());
+        store.dispatch(EntityAction::AddMany(sequential.clone())).expect("entity_reducer can't fail");
+        black_box(store.get_state());
+    });
+
+    let mut large = store(filled(10_000));
+    time("dispatching to a store of 10k", 1000, |i| {
+        let item = Item { id: (i % 10_000) as i32, value: i as u64 };
+        large.dispatch(EntityAction::ReplaceEntity(item)).expect("entity_reducer can't fail");
+    });
+}
@@ -0,0 +1,217 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+#[cfg(feature = "futures")]
+use futures::Stream;
+
+use crate::Error;
+use crate::store::{Store};
+
+// Something run on the store's thread, given the store
+type Job<State, Action, E> = Box<dyn FnOnce(&mut Store<State, Action, E>) + Send>;
+
+enum Message<State, Action, E> {
+    Dispatch(Action),
+    Run(Job<State, Action, E>),
+    // something's been queued through the store's dispatcher
+    Wake,
+    Shutdown,
+}
+
+// The way into a store running on its own thread. Clones reach the same store. Messages from one
+// handle are handled in the order sent.
+pub struct StoreHandle<State, Action, E = Error> {
+    // shared, so the store's dispatcher can wake it without keeping it running once the handles go
+    sender: Arc<Sender<Message<State, Action, E>>>,
+    thread: Arc<Mutex<Option<JoinHandle<()>>>>,
+}
+
+impl<State, Action, E> Clone for StoreHandle<State, Action, E> {
+    fn clone(&self) -> Self {
+        StoreHandle { sender: self.sender.clone(), thread: self.thread.clone() }
+    }
+}
+
+impl<State, Action, E> Store<State, Action, E>
+    where State: Clone + 'static, Action: Clone + Send + 'static, E: 'static {
+
+    // Runs the store build returns on a thread of its own, which every reducer, middleware and
+    // observer is then called on, so they needn't be Send. The store is built there too, as it
+    // can't be sent there once built. What's dispatched through the store's dispatcher, e.g. from
+    // another thread, is dispatched as soon as it's queued.
+    pub fn spawn(build: impl FnOnce() -> Store<State, Action, E> + Send + 'static) -> StoreHandle<State, Action, E> {
+
+        let (sender, receiver) = mpsc::channel();
+        let sender = Arc::new(sender);
+        let waker = Arc::downgrade(&sender);
+
+        let thread = thread::spawn(move || {
+            let mut store = build();
+
+            store.dispatcher().wake_with(move || {
+                if let Some(sender) = waker.upgrade() {
+                    let _ = sender.send(Message::Wake);
+                }
+            });
+
+            // also ends once every handle's gone
+            for message in receiver {
+                match message {
+                    Message::Dispatch(action) => {
+                        let _ = store.dispatch(action);
+                    },
+                    Message::Run(job) => job(&mut store),
+                    Message::Wake => {},
+                    Message::Shutdown => break,
+                }
+
+                // whatever jobs, or other threads, have queued through the dispatcher since
+                let _ = store.process_pending();
+            }
+        });
+
+        StoreHandle { sender, thread: Arc::new(Mutex::new(Some(thread))) }
+    }
+}
+
+impl<State, Action, E> StoreHandle<State, Action, E>
+    where State: Clone + 'static, Action: Clone + Send + 'static, E: 'static {
+
+    // Does nothing once the store's shut down. Nothing waits to hear whether the action was
+    // refused either, so a failure goes unreported.
+    pub fn dispatch(&self, action: Action) {
+        let _ = self.sender.send(Message::Dispatch(action));
+    }
+
+    // Waits for the store to get to it, so it sees everything this handle dispatched before. None
+    // once the store's shut down, or its thread has panicked.
+    pub fn select<S: Send + 'static>(&self, selector: impl FnOnce(&State) -> S + Send + 'static) -> Option<S> {
+        self.run(move |store| selector(&store.get_state()))
+    }
+
+    // As Store::observe_channel, None as for select
+    pub fn observe_channel<S: Send + 'static>(&self, selector: impl Fn(&State) -> S + Send + 'static) -> Option<Receiver<S>> {
+        self.run(move |store| store.observe_channel(selector))
+    }
+
+    // As Store::observe_stream, for consuming on an async runtime. None as for select.
+    #[cfg(feature = "futures")]
+    pub fn observe_stream<S: PartialEq + Clone + Send + 'static>(&self, selector: impl Fn(&State) -> S + Send + 'static) -> Option<impl Stream<Item = S>> {
+        self.run(move |store| store.observe_stream(selector))
+    }
+
+    // None if the store's thread is gone, so never gets to the job
+    fn run<T: Send + 'static>(&self, job: impl FnOnce(&mut Store<State, Action, E>) -> T + Send + 'static) -> Option<T> {
+
+        let (sender, receiver) = mpsc::channel();
+
+        self.sender.send(Message::Run(Box::new(move |store| {
+            let _ = sender.send(job(store));
+        }))).ok()?;
+
+        receiver.recv().ok()
+    }
+
+    // Stops the store once it's handled everything sent before, waiting for its thread to finish.
+    // Later dispatches from any handle are dropped, and shutting down again does nothing. Fails with
+    // the panic if the store's thread panicked.
+    pub fn shutdown(&self) -> thread::Result<()> {
+
+        let _ = self.sender.send(Message::Shutdown);
+
+        let thread = self.thread.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take();
+
+        match thread {
+            Some(thread) => thread.join(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn counting_store() -> Store<i32, i32> {
+        let mut store = Store::new(0);
+        store.register_reducer(Box::new(|count, n| {
+            if *n == 13 {
+                panic!("unlucky");
+            }
+            count + n
+        }));
+        store
+    }
+
+    #[test]
+    fn every_handle_reaches_the_same_store() {
+        let handle = Store::spawn(counting_store);
+        let other = handle.clone();
+
+        thread::spawn(move || other.dispatch(1)).join().unwrap();
+        handle.dispatch(2);
+
+        assert_eq!(handle.select(|count| *count), Some(3));
+    }
+
+    #[test]
+    fn shutting_down_handles_what_was_sent_first() {
+        let handle = Store::spawn(counting_store);
+        let counts = handle.observe_channel(|count| *count).unwrap();
+
+        (1..=3).for_each(|n| handle.dispatch(n));
+        handle.shutdown().unwrap();
+
+        assert_eq!(counts.iter().collect::<Vec<_>>(), vec![1, 3, 6]);
+        assert_eq!(handle.select(|count| *count), None);
+        assert!(handle.observe_channel(|count| *count).is_none());
+        assert!(handle.shutdown().is_ok());
+    }
+
+    #[test]
+    fn a_store_whose_thread_died_is_reported_rather_than_panicking_again() {
+        let handle = Store::spawn(counting_store);
+
+        handle.dispatch(13);
+
+        assert_eq!(handle.select(|count| *count), None);
+        assert!(handle.shutdown().is_err());
+    }
+
+    #[test]
+    fn the_stores_dispatcher_wakes_it() {
+        let (dispatchers, dispatcher) = mpsc::channel();
+        let handle = Store::spawn(move || {
+            let store = counting_store();
+            dispatchers.send(store.dispatcher()).unwrap();
+            store
+        });
+        let counts = handle.observe_channel(|count| *count).unwrap();
+
+        // nothing else is sent to the store, so only the dispatcher can get it to dispatch
+        dispatcher.recv().unwrap().dispatch(5);
+
+        assert_eq!(counts.recv_timeout(Duration::from_secs(1)), Ok(5));
+        handle.shutdown().unwrap();
+    }
+
+
+    #[cfg(feature = "futures")]
+    #[tokio::test]
+    async fn streams_the_selection_to_a_runtime_as_it_changes() {
+        use futures::StreamExt;
+
+        let handle = Store::spawn(counting_store);
+        let counts = handle.observe_stream(|count| *count).unwrap();
+
+        let dispatching = handle.clone();
+        tokio::spawn(async move { [1, 0, 2, 3].iter().for_each(|n| dispatching.dispatch(*n)) });
+
+        // the 0 leaves the count as it was, so isn't streamed
+        let first = tokio::time::timeout(Duration::from_secs(1), counts.take(3).collect::<Vec<_>>()).await;
+        assert_eq!(first.expect("timed out waiting on the stream"), vec![1, 3, 6]);
+        handle.shutdown().unwrap();
+    }
+}
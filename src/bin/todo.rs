@@ -0,0 +1,594 @@
+
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{Duration, SystemTime};
+use tokio::time;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use rs_redux::{combine_reducers, create_selector, entity_reducer, field_changes, on, slice_reducer, try_slice_reducer, undoable};
+use rs_redux::{CheckedAction, Collection, Clock, CollectionDiff, Diffable, DispatchedAction, DispatchError, DispatchMeta, DuplicatePolicy, EntityAction, Error, FallibleReducer, HistoryEntry, Identifiable, IsNoop, LoggerMiddleware, Next, ObserverBand, SharedStore, Store, StoreBuilder, Undoable, UndoableAction, UndoAction};
+#[cfg(feature = "heap-size")]
+use rs_redux::HeapSize;
+#[cfg(feature = "serde")]
+use rs_redux::{PersistConfig, PersistMiddleware};
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct Todo {
+    id: i32,
+    task: String,
+    done: bool,
+}
+
+impl Todo {
+    fn new(id: i32, task: &str) -> Todo {
+        Todo { task: String::from(task), id, done: false }
+    }
+}
+
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+enum TodoAction {
+    Entity(EntityAction<Todo>),
+    MarkDone(i32, bool),
+    ChangeText(i32, String),
+    Reorder(Vec<i32>),
+    History(UndoAction),
+    Noop,
+}
+
+impl IsNoop for TodoAction {
+    fn is_noop(&self) -> bool {
+        matches!(self, TodoAction::Noop)
+    }
+}
+
+impl UndoableAction for TodoAction {
+    fn undo_action(&self) -> Option<UndoAction> {
+        match self {
+            TodoAction::History(undo) => Some(*undo),
+            _ => None,
+        }
+    }
+}
+
+
+#[cfg(feature = "heap-size")]
+impl HeapSize for Todo {
+    fn heap_size(&self) -> usize {
+        self.task.capacity()
+    }
+}
+
+impl Identifiable for Todo {
+    type Id = i32;
+
+    fn get_id(&self) -> i32 {
+        self.id
+    }
+
+    fn set_id(&mut self, id: i32) {
+        self.id = id;
+    }
+}
+
+impl CheckedAction<RootState> for TodoAction {
+    type Error = Error;
+
+    fn check(&self, state: &RootState) -> Result<(), Error> {
+
+        let id = match self {
+            TodoAction::Entity(EntityAction::AddEntity(todo)) if state.todos.policy() == DuplicatePolicy::Error => {
+                if state.todos.contains(&todo.id) {
+                    return Err(Error::DuplicateId(todo.id));
+                }
+                return Ok(());
+            },
+            TodoAction::Entity(EntityAction::ChangeId { new, .. }) if state.todos.contains(new) => {
+                return Err(Error::DuplicateId(*new));
+            },
+            TodoAction::Entity(EntityAction::RemoveEntity(id)) => id,
+            TodoAction::Entity(EntityAction::ChangeId { old, .. }) => old,
+            TodoAction::MarkDone(id, _) => id,
+            TodoAction::ChangeText(id, _) => id,
+            _ => return Ok(()),
+        };
+
+        if state.todos.contains(id) {
+            Ok(())
+        } else {
+            Err(Error::NotFound(*id))
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct RootState {
+    todos: Collection<Todo>,
+    counter: i32,
+}
+
+impl RootState {
+    fn new() -> RootState {
+        RootState { todos: Collection::new_dense(), counter: 0 }
+    }
+}
+
+field_changes!(RootState { todos, counter });
+
+#[derive(Debug, PartialEq)]
+struct RootStateDiff {
+    todos: CollectionDiff<i32>,
+}
+
+impl Diffable for RootState {
+    type Diff = RootStateDiff;
+
+    fn diff(&self, prev: &Self) -> RootStateDiff {
+        RootStateDiff { todos: self.todos.diff(&prev.todos) }
+    }
+}
+
+fn todos_reducer(todos: &Collection<Todo>, action: &TodoAction) -> Result<Collection<Todo>, Error> {
+
+    match action {
+        TodoAction::Entity(x) => Ok(entity_reducer(todos, x)),
+        TodoAction::MarkDone(id, _) | TodoAction::ChangeText(id, _) if !todos.contains(id) => Err(Error::NotFound(*id)),
+        TodoAction::MarkDone(id, done) => Ok(todos.clone().map_one(id, |todo| todo.done = *done)),
+        TodoAction::ChangeText(id, text) => Ok(todos.clone().map_one(id, |todo| todo.task = text.to_owned())),
+        TodoAction::Reorder(order) => todos.clone().reorder(order.clone()),
+        TodoAction::History(_) | TodoAction::Noop => Ok(todos.clone()),
+    }
+
+}
+
+// todos ever added
+fn counter_reducer(counter: &i32, action: &TodoAction) -> i32 {
+    match action {
+        TodoAction::Entity(EntityAction::AddEntity(_)) => counter + 1,
+        _ => *counter,
+    }
+}
+
+fn root_reducer() -> Box<FallibleReducer<RootState, TodoAction>> {
+    combine_reducers(vec![
+        try_slice_reducer(|state: &RootState| state.todos.clone(), |state, todos| state.todos = todos, todos_reducer),
+        slice_reducer(|state: &RootState| state.counter, |state, counter| state.counter = counter, counter_reducer),
+    ])
+}
+
+// so the demo prints the same timestamps every run
+struct FixedClock(SystemTime);
+
+impl Clock for FixedClock {
+    fn now(&self) -> SystemTime {
+        self.0
+    }
+}
+
+fn trim_text(action: DispatchedAction<TodoAction>, next: &mut Next<RootState, TodoAction>) {
+    next.pass(action.map(|action| match action {
+        TodoAction::ChangeText(id, text) => TodoAction::ChangeText(id, text.trim().to_string()),
+        action => action,
+    }))
+}
+
+fn select_id_2_todo_task_done(state: &RootState) -> Option<bool> {
+
+    let todo = state.todos.get(&2);
+
+    todo.map(|t| t.done)
+}
+
+fn all_done(state: &RootState) -> bool {
+    !state.todos.is_empty() && state.todos.count_where(|todo| todo.done) == state.todos.len()
+}
+
+fn test_observer(state: &RootState) -> bool {
+    select_id_2_todo_task_done(state).is_some()
+}
+
+
+
+#[tokio::main]
+async fn main() -> Result<(), DispatchError> {
+
+    let mut interval = time::interval(Duration::from_secs(1));
+    interval.tick().await;
+    println!("Hello, redux!");
+
+
+    let builder = StoreBuilder::new()
+        .with_initial_state(RootState::new())
+        .with_fallible_reducer(root_reducer())
+        .with_noops_skipped();
+    let builder = match std::env::var_os("LOG_ACTIONS") {
+        Some(_) => builder.with_middleware(LoggerMiddleware::new(std::io::stdout())),
+        None => builder,
+    };
+    let mut store = builder.with_middleware(trim_text).build().expect("the store's missing its state or reducer");
+    let root = store.reducer_handles()[0];
+    println!("Building without a reducer fails with {:?}", StoreBuilder::<RootState, TodoAction>::new().with_initial_state(RootState::new()).build().err());
+
+    // editing a todo reopens it
+    store.register_effect(|action, _| match &action.action {
+        TodoAction::ChangeText(id, _) => Some(TodoAction::MarkDone(*id, false)),
+        _ => None,
+    });
+
+    interval.tick().await;
+    // each only worked out again once what it selects from changes
+    let select_todos = create_selector(|state: &RootState| state.todos.clone(), |todos| todos.clone());
+    let select_todo_2 = create_selector(select_todos.selector(), |todos| todos.get(&2).cloned());
+    let select_done = create_selector(select_todo_2.selector(), |todo| todo.as_ref().map(|todo| todo.done));
+    let task_2_set = store.observe_with_prev(select_done.selector(), |was, done| println!("task 2 done? {:?}, was {:?}", done, was));
+    store.observe_with_action_in(ObserverBand::Effect, test_observer, on!(TodoAction::MarkDone(..) | TodoAction::ChangeText(..) => |action, _| println!("todo edited by {:?}", action)));
+
+    interval.tick().await;
+    store.dispatch(TodoAction::Entity(EntityAction::AddEntity(Todo::new(1, "understand &references") )))?;
+    println!("State is {:?}", store.get_state());
+    interval.tick().await;
+    store.dispatch(TodoAction::Entity(EntityAction::AddEntity(Todo::new(2, "get good") )))?;
+    interval.tick().await;
+    store.dispatch(TodoAction::Entity(EntityAction::AddEntity(Todo::new(3, "understand 'lifetimes") )))?;
+    println!("State is {:?}", store.get_state());
+
+    interval.tick().await;
+    // observers hear of these once, along with the last of them
+    store.dispatch_all(vec![TodoAction::MarkDone(1, true), TodoAction::MarkDone(2, true)])?;
+    println!("State is {:?}", store.get_state());
+
+    // there's no todo 42 to mark done, so the state's left as it was
+    let unchanged = store.get_state();
+    println!("Marking a missing todo done fails with {:?}", store.dispatch(TodoAction::MarkDone(42, true)).err());
+    println!("leaving the state as it was? {}", store.get_state() == unchanged);
+    // checked before any reducer runs
+    println!("Removing it fails with {:?}", store.try_dispatch(TodoAction::Entity(EntityAction::RemoveEntity(42))).err());
+
+    store.version_by_equality();
+    let version = store.version();
+    store.dispatch(TodoAction::History(UndoAction::Undo))?;
+    store.dispatch_all(vec![TodoAction::MarkDone(1, false), TodoAction::MarkDone(1, true)])?;
+    println!("An ignored action and a batch cancelling out leave the version at {} (was {})", store.version(), version);
+    store.dispatch(TodoAction::MarkDone(1, false))?;
+    store.dispatch(TodoAction::MarkDone(1, true))?;
+    println!("while two real changes take it to {}", store.version());
+    interval.tick().await;
+    store.dispatch(TodoAction::Entity(EntityAction::RemoveEntity(1)))?;
+    println!("State is {:?}", store.get_state());
+
+    interval.tick().await;
+    store.dispatch(TodoAction::Entity(EntityAction::ReplaceEntity(Todo::new(2, "get gooder") )))?;
+    interval.tick().await;
+    store.dispatch(TodoAction::Entity(EntityAction::ReplaceEntity(Todo::new(2, "get goodest") )))?;
+    interval.tick().await;
+    store.dispatch(TodoAction::Entity(EntityAction::RemoveEntity(2)))?;
+    interval.tick().await;
+    store.dispatch(TodoAction::Entity(EntityAction::AddEntity(Todo::new(2, "get good") )))?;
+
+    interval.tick().await;
+    store.dispatch(TodoAction::ChangeText(2, String::from(" git gud ")))?;
+
+    println!("State is {:?}", store.get_state());
+
+    println!("State select_id_2_todo_task_full is {:?}", store.select(Box::new(select_id_2_todo_task_done)));
+
+    println!("Over {} changes to todos, whether task 2's done was worked out {} times", select_todos.recompute_count(), select_done.recompute_count());
+    store.unsubscribe(task_2_set);
+
+    interval.tick().await;
+    let (todos, deleted) = store.get_state().todos.clone().take(&3);
+    println!("Deleted {:?}, leaving {} todos", deleted, todos.len());
+    if let Some(todo) = deleted {
+        println!("Undone, back to {} todos", todos.add(&todo).len());
+    }
+
+    let todos = store.get_state().todos.clone().with_index("done", |todo: &Todo| todo.done);
+    let todos = todos.map_one(&2, |todo| todo.done = true);
+    println!("Done todos are {:?}", todos.find_by_index("done", &true));
+
+    let page = store.select(Collection::select_page(|state: &RootState| state.todos.clone(), 0, 10));
+    println!("Page {} of {} is {:?}", page.page + 1, page.total_pages, page.items);
+
+    store.dispatch(TodoAction::Entity(EntityAction::AddEntity(Todo::new(-1, "sync with the server"))))?;
+    store.dispatch(TodoAction::Entity(EntityAction::ChangeId { old: -1, new: 4 }))?;
+    println!("Synced todo now has id 4: {:?}", store.get_state().todos.get(&4));
+
+    store.dispatch_thunk(|ctx| {
+        if ctx.get_state().todos.len() < 10 {
+            ctx.dispatch(TodoAction::Entity(EntityAction::AddEntity(Todo::new(5, "write a thunk"))))?;
+            ctx.dispatch(TodoAction::MarkDone(5, true))?;
+        }
+        Ok(())
+    })?;
+
+    let before = store.get_state();
+    store.dispatch(TodoAction::Entity(EntityAction::MoveEntity(2, 0)))?;
+    store.dispatch(TodoAction::MarkDone(3, true))?;
+    println!("Changes since are {:?}", store.changes_since(&before));
+    println!("while the snapshot from before still has {:?}", before.todos.get(&3));
+
+    let reversed: Vec<i32> = store.get_state().todos.ids().iter().rev().cloned().collect();
+    store.dispatch(TodoAction::Reorder(reversed))?;
+    println!("Dragged into reverse, the todos go {:?}", store.get_state().todos.ids());
+    let version = store.version();
+    store.dispatch(TodoAction::Noop)?;
+    println!("A no-op skips the reducers, leaving the version at {} (was {})", store.version(), version);
+
+    let action_of = |entry: &HistoryEntry<RootState, TodoAction>| entry.action.as_ref().map(|dispatched| dispatched.action.clone());
+
+    store.set_clock(FixedClock(SystemTime::UNIX_EPOCH));
+    store.enable_history(10);
+    store.dispatch(TodoAction::MarkDone(2, false))?;
+    store.dispatch(TodoAction::ChangeText(2, String::from("get good again")))?;
+    store.jump_to(1);
+    println!("Back at {:?}, todo 2 is {:?}", action_of(&store.history()[1]), store.get_state().todos.get(&2));
+    store.step_forward();
+    println!("Forward at {:?}, todo 2 is {:?}", action_of(&store.history()[2]), store.get_state().todos.get(&2));
+    store.step_back();
+    store.dispatch_with_meta(TodoAction::MarkDone(2, true), DispatchMeta { correlation_id: Some(String::from("branch")) })?;
+    println!("Branching off there leaves {:?}", store.history().iter().map(action_of).collect::<Vec<_>>());
+    println!("the last as dispatched: {:?}", store.history().last().and_then(|entry| entry.action.as_ref()));
+    let logged: Vec<TodoAction> = store.history().iter().filter_map(action_of).collect();
+    let replayed = store.replay(RootState::clone(&store.history()[0].state), logged.clone())?;
+    println!("Replaying {:?} gets the same state? {}", logged, replayed == *store.get_state());
+    let mut rebuilt = StoreBuilder::new()
+        .with_initial_state(RootState::new())
+        .with_fallible_reducer(root_reducer())
+        .with_observer(|state: &RootState| state.todos.len(), |todos| println!("The rebuilt store has {} todos", todos))
+        .build().expect("the rebuilt store's missing its state or reducer");
+    rebuilt.replay_and_commit(RootState::clone(&store.history()[0].state), logged)?;
+
+    let mut undoable_store = StoreBuilder::new()
+        .with_initial_state(Undoable::new(RootState::new()))
+        .with_fallible_reducer(Box::new(undoable(root_reducer(), 20)))
+        .with_observer(|state: &Undoable<RootState>| state.can_redo(), |can_redo| println!("Can redo? {}", can_redo))
+        .build().expect("the undoable store's missing its state or reducer");
+    undoable_store.dispatch(TodoAction::Entity(EntityAction::AddEntity(Todo::new(1, "don't lose me"))))?;
+    undoable_store.dispatch(TodoAction::Entity(EntityAction::RemoveEntity(1)))?;
+    undoable_store.dispatch(TodoAction::History(UndoAction::Undo))?;
+    println!("Undoing the removal brings back {:?}", undoable_store.get_state().present.todos.get(&1));
+
+    #[cfg(feature = "serde")]
+    {
+        let snapshot = store.snapshot().expect("couldn't snapshot the state");
+        let mut restored: Store<RootState, TodoAction> = Store::new(RootState::new());
+        restored.hydrate(&snapshot).expect("couldn't hydrate the snapshot");
+        println!("Hydrated from a snapshot, todo 2 is {:?}", restored.get_state().todos.get(&2));
+        println!("Hydrating from nonsense fails with {:?}", restored.hydrate("{ not json").err());
+
+        let path = std::env::temp_dir().join("rs-redux-todos.json");
+        {
+            let mut persisted = StoreBuilder::new()
+                .with_initial_state(RootState::new())
+                .with_fallible_reducer(root_reducer())
+                .with_middleware(PersistMiddleware::new(&path, PersistConfig { debounce: Duration::from_millis(20), ..Default::default() }))
+                .build().expect("the persisted store's missing its state or reducer");
+            persisted.dispatch(TodoAction::Entity(EntityAction::AddEntity(Todo::new(1, "survive a restart"))))?;
+            persisted.dispatch(TodoAction::MarkDone(1, true))?;
+        } // dropping the store writes what's waiting
+        let mut restarted: Store<RootState, TodoAction> = Store::new(RootState::new());
+        restarted.rehydrate_from(&path).expect("couldn't rehydrate");
+        println!("After a restart, todo 1 is {:?}", restarted.get_state().todos.get(&1));
+        std::fs::write(&path, "corrupted").expect("couldn't corrupt the file");
+        println!("A corrupted file fails with {:?}, leaving {} todo(s)", restarted.rehydrate_from(&path).err(), restarted.get_state().todos.len());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    let todos = &store.get_state().todos;
+    let understanding = |todo: &Todo| !todo.done && todo.task.contains("understand");
+    println!("{} todo(s) left to understand, first {:?}: {:?}", todos.count_where(understanding), todos.find(understanding), todos.filter(understanding));
+    // e.g. switching off marking todos done for a while
+    let reducer = root_reducer();
+    store.replace_fallible_reducer(root, Box::new(move |state, action| match action {
+        TodoAction::MarkDone(..) => Ok(state.clone()),
+        _ => reducer(state, action),
+    }));
+    store.dispatch(TodoAction::MarkDone(4, true))?;
+    println!("With MarkDone ignored, todo 4 is {:?}", store.get_state().todos.get(&4));
+    store.replace_fallible_reducer(root, root_reducer());
+    store.dispatch(TodoAction::MarkDone(4, true))?;
+    println!("With it back, todo 4 is {:?}", store.get_state().todos.get(&4));
+
+    let dispatcher = store.dispatcher();
+    let celebration = store.observe_distinct(all_done, move |done| if *done {
+        dispatcher.dispatch(TodoAction::Entity(EntityAction::AddEntity(Todo::new(100, "celebrate"))));
+    });
+    let ids: Vec<i32> = store.get_state().todos.ids().iter().cloned().collect();
+    store.dispatch_all(ids.into_iter().map(|id| TodoAction::MarkDone(id, true)))?;
+    println!("All done, so now there's {:?}", store.get_state().todos.get(&100));
+
+    store.unsubscribe(celebration);
+    let lengths = store.observe_channel_distinct(|state: &RootState| state.todos.len());
+    let counter = std::thread::spawn(move || lengths.iter().take(2).collect::<Vec<_>>());
+    store.dispatch(TodoAction::Entity(EntityAction::RemoveEntity(100)))?;
+    store.dispatch(TodoAction::MarkDone(4, false))?;
+    store.dispatch(TodoAction::Entity(EntityAction::RemoveEntity(4)))?;
+    println!("Another thread saw the number of todos go {:?}", counter.join().expect("counter panicked"));
+    // its receiver gone, the channel's observer removes itself here
+    store.dispatch(TodoAction::MarkDone(3, false))?;
+
+    let renders = Rc::new(Cell::new(0));
+    let counted = renders.clone();
+    let rendering = store.subscribe(move |_| counted.set(counted.get() + 1));
+    store.dispatch(TodoAction::MarkDone(3, true))?;
+    store.dispatch_all(vec![TodoAction::MarkDone(3, false), TodoAction::MarkDone(3, true)])?;
+    store.unsubscribe(rendering);
+    store.dispatch(TodoAction::MarkDone(3, false))?;
+    println!("Rendered {} times over 4 dispatches, 2 of them batched and 1 after unsubscribing", renders.get());
+
+    let panics = Rc::new(Cell::new(0));
+    let counted = panics.clone();
+    store.on_observer_error(move |subscription, message| {
+        counted.set(counted.get() + 1);
+        println!("Observer {} panicked: {}", subscription.id(), message);
+    });
+    store.limit_observer_panics(2);
+    store.subscribe(|state| if state.todos.contains(&3) { panic!("can't render todo 3") });
+    store.dispatch(TodoAction::MarkDone(3, true))?;
+    store.dispatch(TodoAction::MarkDone(3, false))?;
+    store.dispatch(TodoAction::MarkDone(3, true))?;
+    println!("It panicked {} times before being unsubscribed, the others carrying on regardless", panics.get());
+
+    let senders: Vec<_> = (0..2).map(|sender| {
+        let dispatcher = store.dispatcher();
+        std::thread::spawn(move || (0..100).for_each(|i| {
+            dispatcher.dispatch(TodoAction::Entity(EntityAction::AddEntity(Todo::new(1000 + sender * 100 + i, "queued from a thread"))));
+        }))
+    }).collect();
+    senders.into_iter().for_each(|sender| sender.join().expect("sender panicked"));
+    store.process_pending()?;
+    println!("After processing what 2 threads queued there are {} todos", store.get_state().todos.len());
+
+    // as a game loop would, applying what came up during a frame at its end
+    store.dispatch(TodoAction::MarkDone(3, false))?;
+    store.dispatch_deferred(TodoAction::MarkDone(3, true));
+    store.dispatch_deferred(TodoAction::MarkDone(42, true));
+    println!("Deferred, todo 3 is still {:?}", store.get_state().todos.get(&3));
+    let dispatcher = store.dispatcher();
+    let reopening = store.observe(|state: &RootState| state.todos.get(&3).map(|todo| todo.done), move |done| if *done == Some(true) {
+        dispatcher.defer(TodoAction::MarkDone(3, false));
+    });
+    println!("Processing applied {} actions, one of them deferred by an observer, and refused the missing todo", store.process());
+    println!("leaving todo 3 {:?}", store.get_state().todos.get(&3));
+    store.unsubscribe(reopening);
+
+    // on a thread of its own, as its effects' runtime can't be started or stopped inside this one
+    #[cfg(feature = "futures")]
+    std::thread::spawn(|| -> Result<(), DispatchError> {
+        let runtime = tokio::runtime::Runtime::new().expect("couldn't start a runtime for effects");
+        let mut store = StoreBuilder::new()
+            .with_initial_state(RootState::new())
+            .with_fallible_reducer(root_reducer())
+            .build().expect("the saving store's missing its state or reducer");
+
+        // as if saving an edit to a server, which then confirms the todo's done
+        store.register_async_effect(runtime.handle().clone(), |action, _| async move {
+            match action.action {
+                TodoAction::ChangeText(id, _) => {
+                    time::delay_for(Duration::from_millis(10)).await;
+                    Some(TodoAction::MarkDone(id, true))
+                },
+                _ => None,
+            }
+        });
+
+        store.dispatch(TodoAction::Entity(EntityAction::AddEntity(Todo::new(1, "save me"))))?;
+        store.dispatch(TodoAction::ChangeText(1, String::from("saved")))?;
+        std::thread::sleep(Duration::from_millis(50));
+        store.process_pending()?;
+        println!("Once saved, todo 1 is {:?}", store.get_state().todos.get(&1));
+        Ok(())
+    }).join().expect("saving panicked")?;
+
+    println!("{} todos added in all, {} still here", store.get_state().counter, store.get_state().todos.len());
+
+    let shared = SharedStore::new(RootState::new());
+    shared.register_fallible_reducer(|state: &RootState, action: &TodoAction| {
+        Ok(RootState { todos: todos_reducer(&state.todos, action)?, counter: state.counter })
+    });
+    let workers: Vec<_> = (0..4).map(|worker| {
+        let shared = shared.clone();
+        std::thread::spawn(move || (0..5).try_for_each(|i| {
+            shared.dispatch(TodoAction::Entity(EntityAction::AddEntity(Todo::new(worker * 5 + i, "added from a thread"))))
+        }))
+    }).collect();
+    for worker in workers {
+        worker.join().expect("worker panicked")?;
+    }
+    println!("4 threads added {} todos", shared.select(|state| state.todos.len()));
+
+    let handle = Store::spawn(|| {
+        StoreBuilder::new()
+            .with_initial_state(RootState::new())
+            .with_fallible_reducer(root_reducer())
+            .build().expect("the spawned store's missing its state or reducer")
+    });
+    let added = handle.observe_channel(|state: &RootState| state.counter).expect("the store's thread stopped");
+    let other = handle.clone();
+    std::thread::spawn(move || other.dispatch(TodoAction::Entity(EntityAction::AddEntity(Todo::new(1, "sent from another thread")))))
+        .join().expect("sender panicked");
+    handle.dispatch(TodoAction::Entity(EntityAction::AddEntity(Todo::new(2, "sent to the store's thread"))));
+    println!("The store's thread has {} todos", handle.select(|state: &RootState| state.todos.len()).expect("the store's thread stopped"));
+    #[cfg(feature = "futures")]
+    {
+        use futures::StreamExt;
+
+        let lengths = handle.observe_stream(|state: &RootState| state.todos.len()).expect("the store's thread stopped");
+        (3..6).for_each(|id| handle.dispatch(TodoAction::Entity(EntityAction::AddEntity(Todo::new(id, "streamed")))));
+        let lengths = time::timeout(Duration::from_secs(1), lengths.take(3).collect::<Vec<_>>()).await;
+        println!("Streamed the number of todos going {:?}", lengths);
+    }
+    handle.shutdown().expect("the store's thread panicked");
+    println!("It counted {:?} as they were added", added.iter().collect::<Vec<_>>());
+
+    Ok(())
+
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    fn todo_store() -> Store<RootState, TodoAction> {
+        StoreBuilder::new()
+            .with_initial_state(RootState::new())
+            .with_fallible_reducer(root_reducer())
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn checked_actions_on_a_missing_todo_are_refused() {
+        let mut store = todo_store();
+        store.dispatch(TodoAction::Entity(EntityAction::AddEntity(Todo::new(1, "present")))).unwrap();
+        let (state, version) = (store.get_state(), store.version());
+
+        let missing = vec![
+            TodoAction::MarkDone(2, true),
+            TodoAction::ChangeText(3, "changed".to_string()),
+            TodoAction::Entity(EntityAction::RemoveEntity(4)),
+        ];
+        for (action, id) in missing.into_iter().zip(2..) {
+            assert!(matches!(store.try_dispatch(action), Err(Error::NotFound(missing)) if missing == id));
+        }
+
+        assert_eq!(store.get_state(), state);
+        assert_eq!(store.version(), version);
+        assert!(store.try_dispatch(TodoAction::MarkDone(1, true)).is_ok());
+    }
+
+    #[test]
+    fn replaying_the_recorded_actions_reaches_the_live_state() {
+        let mut live = todo_store();
+        let recorded = Rc::new(RefCell::new(vec![]));
+        let recording = recorded.clone();
+        live.apply_middleware(move |action: DispatchedAction<TodoAction>, next: &mut Next<RootState, TodoAction>| {
+            recording.borrow_mut().push(action.action.clone());
+            next.pass(action);
+        });
+
+        live.dispatch(TodoAction::Entity(EntityAction::AddEntity(Todo::new(1, "first")))).unwrap();
+        live.dispatch(TodoAction::Entity(EntityAction::AddEntity(Todo::new(2, "second")))).unwrap();
+        live.dispatch(TodoAction::MarkDone(1, true)).unwrap();
+        live.dispatch(TodoAction::Entity(EntityAction::RemoveEntity(2))).unwrap();
+
+        let mut fresh = todo_store();
+        let notified = Rc::new(Cell::new(0));
+        let counted = notified.clone();
+        fresh.subscribe(move |_| counted.set(counted.get() + 1));
+
+        let actions = recorded.borrow().clone();
+        assert_eq!(fresh.replay(RootState::new(), actions.clone()).unwrap(), *live.get_state());
+        assert_eq!(notified.get(), 0);
+
+        fresh.replay_and_commit(RootState::new(), actions).unwrap();
+        assert_eq!(fresh.get_state(), live.get_state());
+        assert_eq!(notified.get(), 1);
+    }
+}
@@ -0,0 +1,146 @@
+use crate::Error;
+use crate::store::{FallibleReducer, IdempotencyKey, IsNoop, Middleware, Reducer, Store};
+
+// What build does to the new store, in the order given
+type Step<State, Action, E> = Box<dyn FnOnce(&mut Store<State, Action, E>)>;
+
+// The way to put a store together, e.g.
+// StoreBuilder::new().with_initial_state(RootState::new()).with_reducer(root_reducer()).build()?
+// Reducers run, middlewares handle and observers are notified in the order they're given, as if
+// registered one by one.
+pub struct StoreBuilder<State, Action, E = Error> {
+    state: Option<State>,
+    reducers: usize,
+    steps: Vec<Step<State, Action, E>>,
+}
+
+impl<State, Action, E> Default for StoreBuilder<State, Action, E> {
+    fn default() -> Self {
+        StoreBuilder { state: None, reducers: 0, steps: vec![] }
+    }
+}
+
+impl<State, Action> StoreBuilder<State, Action>
+    where State: Clone + 'static, Action: Clone + 'static {
+
+    // for reducers failing with the crate's Error; StoreBuilder::<_, _, E>::default() for others
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<State, Action, E> StoreBuilder<State, Action, E>
+    where State: Clone + 'static, Action: Clone + 'static, E: 'static {
+
+    pub fn with_initial_state(mut self, state: State) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    pub fn with_reducer(self, reducer: Box<Reducer<State, Action>>) -> Self {
+        self.with_fallible_reducer(Box::new(move |state, action| Ok(reducer(state, action))))
+    }
+
+    pub fn with_fallible_reducer(mut self, reducer: Box<FallibleReducer<State, Action, E>>) -> Self {
+        self.reducers += 1;
+        self.step(move |store| {
+            store.register_fallible_reducer(reducer);
+        })
+    }
+
+    pub fn with_middleware(self, middleware: impl Middleware<State, Action, E> + 'static) -> Self {
+        self.step(move |store| {
+            store.apply_middleware(middleware);
+        })
+    }
+
+    // As Store::observe, with no way to unsubscribe, so for observers living as long as the store
+    pub fn with_observer<S: 'static>(self, selector: impl Fn(&State) -> S + 'static, observer: impl Fn(&S) + 'static) -> Self {
+        self.step(move |store| {
+            store.observe(selector, observer);
+        })
+    }
+
+    // As Store::enable_history, from the initial state
+    pub fn with_history(self, capacity: usize) -> Self {
+        self.step(move |store| store.enable_history(capacity))
+    }
+
+    // As Store::skip_noops
+    pub fn with_noops_skipped(self) -> Self where Action: IsNoop {
+        self.step(|store| {
+            store.skip_noops();
+        })
+    }
+
+    // As Store::retain_idempotency_keys
+    pub fn with_idempotency_keys(self, window: usize) -> Self where Action: IdempotencyKey {
+        self.step(move |store| store.retain_idempotency_keys(window))
+    }
+
+    // As Store::version_by_equality
+    pub fn with_version_by_equality(self) -> Self where State: PartialEq {
+        self.step(|store| {
+            store.version_by_equality();
+        })
+    }
+
+    // As Store::coalesce_transactions
+    pub fn with_coalesced_transactions(self) -> Self {
+        self.step(|store| {
+            store.coalesce_transactions();
+        })
+    }
+
+    fn step(mut self, step: impl FnOnce(&mut Store<State, Action, E>) + 'static) -> Self {
+        self.steps.push(Box::new(step));
+        self
+    }
+
+    // Fails without an initial state, or a reducer, as nothing dispatched would change the state
+    pub fn build(self) -> Result<Store<State, Action, E>, Error> {
+
+        let state = self.state.ok_or(Error::NoInitialState)?;
+
+        if self.reducers == 0 {
+            return Err(Error::NoReducers);
+        }
+
+        let mut store = Store::with_error(state);
+        self.steps.into_iter().for_each(|step| step(&mut store));
+
+        Ok(store)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn building_needs_a_state_and_a_reducer() {
+        assert!(matches!(StoreBuilder::<i32, i32>::new().with_reducer(Box::new(|count, n| count + n)).build(), Err(Error::NoInitialState)));
+        assert!(matches!(StoreBuilder::<i32, i32>::new().with_initial_state(0).build(), Err(Error::NoReducers)));
+    }
+
+    #[test]
+    fn reducers_and_observers_keep_the_order_given() {
+        let seen = Arc::new(Mutex::new(vec![]));
+        let first = seen.clone();
+        let second = seen.clone();
+
+        let mut store = StoreBuilder::new()
+            .with_initial_state(1)
+            .with_observer(|count: &i32| *count, move |count| first.lock().unwrap().push(("first", *count)))
+            .with_reducer(Box::new(|count, n| count + n))
+            .with_reducer(Box::new(|count, _| count * 10))
+            .with_observer(|count: &i32| count % 7, move |rest| second.lock().unwrap().push(("second", *rest)))
+            .build()
+            .unwrap();
+
+        store.dispatch(2).unwrap();
+        assert_eq!(*store.get_state(), 30);
+        assert_eq!(*seen.lock().unwrap(), vec![("first", 30), ("second", 2)]);
+    }
+}
@@ -0,0 +1,2179 @@
+use std::any::Any;
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use im::Vector;
+use std::fmt::Debug;
+use std::convert::TryFrom;
+use std::iter::FromIterator;
+use std::sync::{Arc, Mutex, Weak};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error as _};
+
+use crate::{Diffable, Error, Identifiable, Selector, Store, SubscriptionHandle};
+#[cfg(feature = "heap-size")]
+use crate::HeapSize;
+
+// Ids in current order for added, changed & moved, previous order for removed. moved is the fewest
+// ids in both whose moving explains the new order, so dragging one entity moves just that one.
+// Empty exactly when the collections are equal.
+#[derive(Debug, PartialEq)]
+pub struct CollectionDiff<Id> {
+    pub added: Vec<Id>,
+    pub removed: Vec<Id>,
+    pub changed: Vec<Id>,
+    pub moved: Vec<Id>,
+}
+
+impl<Id> CollectionDiff<Id> {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty() && self.moved.is_empty()
+    }
+}
+
+// min & max are None for an empty collection, where sum is zero (N's default)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FieldAggregate<N> {
+    pub count: usize,
+    pub sum: N,
+    pub min: Option<N>,
+    pub max: Option<N>,
+}
+
+// One page of a collection, in ids order. A page past the end (or a page_size of 0, which makes
+// no pages at all) has no items.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub page: usize,
+    pub total_items: usize,
+    pub total_pages: usize,
+}
+
+// What Collection::repair had to fix
+#[derive(Debug, PartialEq)]
+pub struct RepairReport<Id> {
+    pub duplicate_ids: Vec<Id>,
+    pub missing_entities: Vec<Id>,
+    // in no particular order, as they had no place among the ids
+    pub orphaned_entities: Vec<Id>,
+}
+
+impl<Id> RepairReport<Id> {
+    pub fn is_clean(&self) -> bool {
+        self.duplicate_ids.is_empty() && self.missing_entities.is_empty() && self.orphaned_entities.is_empty()
+    }
+}
+
+// The first way Collection::validate found ids and entities out of step
+#[derive(Debug, PartialEq)]
+pub enum CollectionInvariantError<Id> {
+    // in ids, but with no entity
+    MissingEntity(Id),
+    // in ids more than once
+    DuplicateId(Id),
+    // an entity whose id isn't in ids
+    OrphanedEntity(Id),
+    // in ids somewhere other than where its entity's key puts it
+    MisplacedId(Id),
+}
+
+// Which entity Collection::merge keeps for an id in both collections. Custom is given this
+// collection's entity first, and must keep the id.
+pub enum MergePolicy<T> {
+    PreferSelf,
+    PreferOther,
+    Custom(fn(&T, &T) -> T),
+}
+
+// What adding an entity whose id is already in a collection does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicatePolicy {
+    // the existing entity is kept
+    #[default]
+    Ignore,
+    // the existing entity is replaced, keeping its position
+    Replace,
+    // try_add fails with Error::DuplicateId; add, which can't fail, ignores it instead
+    Error,
+}
+
+// Ids that can double as an index, for Collection::new_dense. None for those that can't (e.g.
+// negative ones), which a dense collection keeps in a map instead.
+pub trait DenseId {
+    fn dense_index(&self) -> Option<usize>;
+}
+
+macro_rules! dense_id {
+    ($($int:ty),+) => {
+        $(impl DenseId for $int {
+            fn dense_index(&self) -> Option<usize> {
+                usize::try_from(*self).ok()
+            }
+        })+
+    };
+}
+
+dense_id!(i32, i64, u32, u64, usize);
+
+// Ids from here up are kept in the map too, so one large id can't make a dense collection allocate
+// a slot for every id below it
+const DENSE_LIMIT: usize = 1 << 20;
+
+// Derived views, keyed by descriptor. Only weak references are held, so a view lives as long as
+// some consumer does.
+type ViewCache = Mutex<HashMap<&'static str, Weak<dyn Any + Send + Sync>>>;
+
+pub type SortComparer<T> = fn(&T, &T) -> std::cmp::Ordering;
+
+// What a secondary index files an entity under
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum IndexKey {
+    Bool(bool),
+    Int(i64),
+    Text(String),
+}
+
+impl From<bool> for IndexKey {
+    fn from(key: bool) -> Self {
+        IndexKey::Bool(key)
+    }
+}
+
+impl From<i32> for IndexKey {
+    fn from(key: i32) -> Self {
+        IndexKey::Int(key.into())
+    }
+}
+
+impl From<i64> for IndexKey {
+    fn from(key: i64) -> Self {
+        IndexKey::Int(key)
+    }
+}
+
+impl From<&str> for IndexKey {
+    fn from(key: &str) -> Self {
+        IndexKey::Text(key.to_string())
+    }
+}
+
+impl From<String> for IndexKey {
+    fn from(key: String) -> Self {
+        IndexKey::Text(key)
+    }
+}
+
+// Ids by key, each bucket in the order its ids were filed. Empty buckets are dropped.
+struct SecondaryIndex<T: Identifiable> {
+    key_of: Arc<dyn Fn(&T) -> IndexKey + Send + Sync>,
+    buckets: im::HashMap<IndexKey, Vector<T::Id>>,
+}
+
+impl<T: Identifiable> Clone for SecondaryIndex<T> {
+    fn clone(&self) -> Self {
+        SecondaryIndex { key_of: self.key_of.clone(), buckets: self.buckets.clone() }
+    }
+}
+
+impl<T: Identifiable> SecondaryIndex<T> {
+    fn file(&mut self, id: &T::Id, entity: &T) {
+        self.buckets.entry((self.key_of)(entity)).or_default().push_back(id.clone());
+    }
+
+    fn unfile(&mut self, id: &T::Id, entity: &T) {
+        let key = (self.key_of)(entity);
+        if let Some(bucket) = self.buckets.get_mut(&key) {
+            bucket.retain(|filed| filed != id);
+            if bucket.is_empty() {
+                self.buckets.remove(&key);
+            }
+        }
+    }
+}
+
+// The ordered entities, as one structure: every id is in the order exactly once, with its entity,
+// which is kept with a key for where the id is in the order. Keys go up along the order, so an id's
+// position is found by binary search rather than a scan. Its parts are private to this module, so
+// nothing else can let them drift apart. All are persistent, so clones share them and a change
+// copies only the path to it. Entities are held in Arcs, so that path holds refcounts rather than
+// entity copies.
+mod entries {
+    use std::collections::HashSet;
+    use std::sync::Arc;
+    use im::Vector;
+
+    use crate::Identifiable;
+    use super::{CollectionInvariantError, Iter, RepairReport};
+
+    // Between the keys of neighbouring ids when they're renumbered, leaving room to insert between
+    // them many times over before needing to again
+    const KEY_GAP: u64 = 1 << 32;
+
+    // An entity, and the key for where its id is in the order
+    struct Slot<T> {
+        key: u64,
+        entity: Arc<T>,
+    }
+
+    impl<T> Clone for Slot<T> {
+        fn clone(&self) -> Self {
+            Slot { key: self.key, entity: self.entity.clone() }
+        }
+    }
+
+    // Where entities are kept by id
+    enum Slots<T: Identifiable> {
+        Hashed(im::HashMap<T::Id, Slot<T>>),
+        // an entity at its id's index, or in overflow if the id has none (e.g. it's negative)
+        Dense { slots: Vector<Option<Slot<T>>>, overflow: im::HashMap<T::Id, Slot<T>>, index_of: fn(&T::Id) -> Option<usize> },
+    }
+
+    impl<T: Identifiable> Clone for Slots<T> {
+        fn clone(&self) -> Self {
+            match self {
+                Slots::Hashed(entities) => Slots::Hashed(entities.clone()),
+                Slots::Dense { slots, overflow, index_of } => Slots::Dense { slots: slots.clone(), overflow: overflow.clone(), index_of: *index_of },
+            }
+        }
+    }
+
+    impl<T: Identifiable> Slots<T> {
+        // empty, of the same kind
+        fn empty_like(&self) -> Slots<T> {
+            match self {
+                Slots::Hashed(_) => Slots::Hashed(im::HashMap::new()),
+                Slots::Dense { index_of, .. } => Slots::Dense { slots: Vector::new(), overflow: im::HashMap::new(), index_of: *index_of },
+            }
+        }
+
+        fn get(&self, id: &T::Id) -> Option<&Slot<T>> {
+            match self {
+                Slots::Hashed(entities) => entities.get(id),
+                Slots::Dense { slots, overflow, index_of } => match index_of(id) {
+                    Some(i) => slots.get(i).and_then(Option::as_ref),
+                    None => overflow.get(id),
+                },
+            }
+        }
+
+        fn get_mut(&mut self, id: &T::Id) -> Option<&mut Slot<T>> {
+            match self {
+                Slots::Hashed(entities) => entities.get_mut(id),
+                Slots::Dense { slots, overflow, index_of } => match index_of(id) {
+                    Some(i) => slots.get_mut(i).and_then(Option::as_mut),
+                    None => overflow.get_mut(id),
+                },
+            }
+        }
+
+        fn insert(&mut self, id: T::Id, slot: Slot<T>) -> Option<Slot<T>> {
+            match self {
+                Slots::Hashed(entities) => entities.insert(id, slot),
+                Slots::Dense { slots, overflow, index_of } => match index_of(&id) {
+                    Some(i) => {
+                        while slots.len() <= i {
+                            slots.push_back(None);
+                        }
+                        slots.set(i, Some(slot))
+                    },
+                    None => overflow.insert(id, slot),
+                },
+            }
+        }
+
+        fn remove(&mut self, id: &T::Id) -> Option<Slot<T>> {
+            match self {
+                Slots::Hashed(entities) => entities.remove(id),
+                Slots::Dense { slots, overflow, index_of } => match index_of(id) {
+                    Some(i) if i < slots.len() => slots.set(i, None),
+                    Some(_) => None,
+                    None => overflow.remove(id),
+                },
+            }
+        }
+
+        // in no particular order
+        fn values(&self) -> Box<dyn Iterator<Item = &Slot<T>> + '_> {
+            match self {
+                Slots::Hashed(entities) => Box::new(entities.values()),
+                Slots::Dense { slots, overflow, .. } => Box::new(slots.iter().flatten().chain(overflow.values())),
+            }
+        }
+
+        fn ptr_eq(&self, other: &Self) -> bool {
+            match (self, other) {
+                (Slots::Hashed(a), Slots::Hashed(b)) => a.ptr_eq(b),
+                (Slots::Dense { slots: a, overflow: a_overflow, .. }, Slots::Dense { slots: b, overflow: b_overflow, .. }) => {
+                    a.ptr_eq(b) && a_overflow.ptr_eq(b_overflow)
+                },
+                _ => false,
+            }
+        }
+    }
+
+    pub(super) struct Entries<T: Identifiable + Clone> {
+        ids: Vector<T::Id>,
+        slots: Slots<T>,
+    }
+
+    impl<T: Identifiable + Clone> Clone for Entries<T> {
+        fn clone(&self) -> Self {
+            Entries { ids: self.ids.clone(), slots: self.slots.clone() }
+        }
+    }
+
+    // the same ids in the same order, with equal entities, whichever way each side keeps them
+    impl<T: Identifiable + Clone + PartialEq> PartialEq for Entries<T> {
+        fn eq(&self, other: &Self) -> bool {
+            self.ids == other.ids && self.ids.iter().all(|id| match (self.get_shared(id), other.get_shared(id)) {
+                (Some(a), Some(b)) => Arc::ptr_eq(&a, &b) || a == b,
+                (a, b) => a.is_none() && b.is_none(),
+            })
+        }
+    }
+
+    impl<T: Identifiable + Clone> Entries<T> {
+        pub(super) fn new() -> Entries<T> {
+            Entries { ids: Vector::new(), slots: Slots::Hashed(im::HashMap::new()) }
+        }
+
+        // index_of gives an id's slot, or None to keep it in a map instead
+        pub(super) fn dense(index_of: fn(&T::Id) -> Option<usize>) -> Entries<T> {
+            Entries { ids: Vector::new(), slots: Slots::Dense { slots: Vector::new(), overflow: im::HashMap::new(), index_of } }
+        }
+
+        // empty, keeping entities the same way
+        pub(super) fn empty_like(&self) -> Entries<T> {
+            Entries { ids: Vector::new(), slots: self.slots.empty_like() }
+        }
+
+        pub(super) fn len(&self) -> usize {
+            self.ids.len()
+        }
+
+        pub(super) fn get(&self, id: &T::Id) -> Option<&T> {
+            self.slots.get(id).map(|slot| slot.entity.as_ref())
+        }
+
+        pub(super) fn get_shared(&self, id: &T::Id) -> Option<Arc<T>> {
+            self.slots.get(id).map(|slot| slot.entity.clone())
+        }
+
+        pub(super) fn contains(&self, id: &T::Id) -> bool {
+            self.slots.get(id).is_some()
+        }
+
+        pub(super) fn ids(&self) -> &Vector<T::Id> {
+            &self.ids
+        }
+
+        // in ids order
+        pub(super) fn iter(&self) -> Iter<'_, T> {
+            Iter { ids: self.ids.iter(), entries: self }
+        }
+
+        // in no particular order, for when order doesn't matter
+        pub(super) fn values(&self) -> impl Iterator<Item = &T> {
+            self.slots.values().map(|slot| slot.entity.as_ref())
+        }
+
+        fn key(&self, id: &T::Id) -> u64 {
+            self.slots.get(id).expect("every id has an entity").key
+        }
+
+        // O(log n) lookups, searching ids by key
+        pub(super) fn position(&self, id: &T::Id) -> Option<usize> {
+            let key = self.slots.get(id)?.key;
+            self.ids.binary_search_by(|other| self.key(other).cmp(&key)).ok()
+        }
+
+        pub(super) fn ptr_eq(&self, other: &Self) -> bool {
+            self.ids.ptr_eq(&other.ids) && self.slots.ptr_eq(&other.slots)
+        }
+
+        // A key between those of the ids either side of position, if there's room for one
+        fn key_at(&self, position: usize) -> Option<u64> {
+
+            let before = position.checked_sub(1).map(|i| self.key(&self.ids[i]));
+            let after = self.ids.get(position).map(|id| self.key(id));
+
+            match (before, after) {
+                (None, None) => Some(KEY_GAP),
+                (Some(before), None) => before.checked_add(KEY_GAP),
+                (None, Some(after)) => after.checked_sub(1).map(|last| last / 2),
+                (Some(before), Some(after)) if after - before > 1 => Some(before + (after - before) / 2),
+                _ => None,
+            }
+        }
+
+        // spreads the keys out evenly again, in ids order
+        fn renumber(&mut self) {
+            for (i, id) in self.ids.iter().enumerate() {
+                if let Some(slot) = self.slots.get_mut(id) {
+                    slot.key = (i as u64 + 1) * KEY_GAP;
+                }
+            }
+        }
+
+        // puts a new id in at position, given there's no entity for it yet
+        fn place(&mut self, position: usize, id: T::Id, entity: Arc<T>) {
+
+            let position = position.min(self.ids.len());
+
+            let key = match self.key_at(position) {
+                Some(key) => key,
+                None => {
+                    self.renumber();
+                    self.key_at(position).expect("room between keys once renumbered")
+                },
+            };
+
+            self.ids.insert(position, id.clone());
+            self.slots.insert(id, Slot { key, entity });
+        }
+
+        // An id not yet present goes in at position (clamped to the end); one already present is
+        // replaced where it is. Returns the entity replaced.
+        pub(super) fn insert(&mut self, position: usize, id: T::Id, entity: T) -> Option<Arc<T>> {
+
+            if let Some(slot) = self.slots.get_mut(&id) {
+                return Some(std::mem::replace(&mut slot.entity, Arc::new(entity)));
+            }
+
+            self.place(position, id, Arc::new(entity));
+
+            None
+        }
+
+        // Changes an entity where it is, copying it only if another version of the collection shares
+        // it. False if the id is missing.
+        pub(super) fn modify(&mut self, id: &T::Id, f: impl FnOnce(&mut T)) -> bool {
+            match self.slots.get_mut(id) {
+                Some(slot) => {
+                    f(Arc::make_mut(&mut slot.entity));
+                    true
+                },
+                None => false,
+            }
+        }
+
+        pub(super) fn remove(&mut self, id: &T::Id) -> Option<Arc<T>> {
+
+            let position = self.position(id)?;
+
+            self.ids.remove(position);
+
+            self.slots.remove(id).map(|slot| slot.entity)
+        }
+
+        // Moves an id to index (clamped to the end), keeping its entity. False if it's missing.
+        pub(super) fn move_to(&mut self, id: &T::Id, index: usize) -> bool {
+            match self.remove(id) {
+                Some(entity) => {
+                    self.place(index, id.clone(), entity);
+                    true
+                },
+                None => false,
+            }
+        }
+
+        // removes every entry matching pred in one pass, returning them in ids order
+        pub(super) fn remove_where(&mut self, pred: impl Fn(&T::Id, &T) -> bool) -> Vec<(T::Id, Arc<T>)> {
+
+            let mut removed = vec![];
+            let slots = &mut self.slots;
+
+            // what's left keeps its keys, which still go up along ids
+            self.ids.retain(|id| {
+                if slots.get(id).is_some_and(|slot| pred(id, &slot.entity)) {
+                    removed.extend(slots.remove(id).map(|slot| (id.clone(), slot.entity)));
+                    false
+                } else {
+                    true
+                }
+            });
+
+            removed
+        }
+
+        // order must hold exactly the ids already present
+        pub(super) fn set_order(&mut self, order: Vector<T::Id>) {
+
+            debug_assert!(
+                order.len() == self.ids.len() && order.iter().all(|id| self.contains(id)),
+                "set_order must be given a permutation of the ids"
+            );
+
+            self.ids = order;
+            self.renumber();
+        }
+
+        // a stable sort by entity
+        pub(super) fn sort_by(&mut self, compare: impl Fn(&T, &T) -> std::cmp::Ordering) {
+
+            let slots = &self.slots;
+            let entity = |id: &T::Id| &slots.get(id).expect("every id has an entity").entity;
+            let mut ids: Vec<T::Id> = self.ids.iter().cloned().collect();
+
+            // Vector's own sort isn't stable
+            ids.sort_by(|a, b| compare(entity(a), entity(b)));
+
+            self.ids = ids.into_iter().collect();
+            self.renumber();
+        }
+
+        // Checks every id has an entity, every entity's id is in ids exactly once (so the lengths
+        // match too), and the keys go up along ids, which the methods above maintain
+        pub(super) fn validate(&self) -> Result<(), CollectionInvariantError<T::Id>> {
+
+            let mut seen = HashSet::with_capacity(self.ids.len());
+            let mut last_key = None;
+
+            for id in &self.ids {
+                let key = match self.slots.get(id) {
+                    Some(slot) => slot.key,
+                    None => return Err(CollectionInvariantError::MissingEntity(id.clone())),
+                };
+                if !seen.insert(id) {
+                    return Err(CollectionInvariantError::DuplicateId(id.clone()));
+                }
+                if last_key.is_some_and(|last| key <= last) {
+                    return Err(CollectionInvariantError::MisplacedId(id.clone()));
+                }
+                last_key = Some(key);
+            }
+
+            match self.values().map(T::get_id).find(|id| !seen.contains(&id)) {
+                Some(id) => Err(CollectionInvariantError::OrphanedEntity(id)),
+                None => Ok(()),
+            }
+        }
+
+        // As if ids and entities had got out of step, with keys going up along entities, to test
+        // validate and repair against
+        #[cfg(test)]
+        pub(super) fn out_of_step(ids: Vec<T::Id>, entities: Vec<T>) -> Entries<T> {
+
+            let mut slots = Slots::Hashed(im::HashMap::new());
+
+            for (i, entity) in entities.into_iter().enumerate() {
+                slots.insert(entity.get_id(), Slot { key: (i as u64 + 1) * KEY_GAP, entity: Arc::new(entity) });
+            }
+
+            Entries { ids: ids.into_iter().collect(), slots }
+        }
+
+        // Keeps the first occurrence of each id, and drops ids without an entity as well as entities
+        // without an id
+        pub(super) fn repair(self) -> (Entries<T>, RepairReport<T::Id>) {
+
+            let mut repaired = self.empty_like();
+            let mut slots = self.slots;
+            let mut report = RepairReport { duplicate_ids: vec![], missing_entities: vec![], orphaned_entities: vec![] };
+
+            for id in self.ids {
+                if repaired.contains(&id) {
+                    report.duplicate_ids.push(id);
+                } else if let Some(slot) = slots.remove(&id) {
+                    let end = repaired.len();
+                    repaired.place(end, id, slot.entity);
+                } else {
+                    report.missing_entities.push(id);
+                }
+            }
+
+            report.orphaned_entities = slots.values().map(|slot| slot.entity.get_id()).collect();
+
+            (repaired, report)
+        }
+    }
+}
+
+use entries::Entries;
+
+// Entities only change through put and evict, which keep the indexes in step
+pub struct Collection<T: Identifiable + Clone> {
+    entries: Entries<T>,
+    indexes: HashMap<&'static str, SecondaryIndex<T>>,
+    views: ViewCache,
+    policy: DuplicatePolicy,
+    sort_comparer: Option<SortComparer<T>>,
+}
+
+// views are a cache of this exact collection, so clones (which then go on to be changed) start empty
+impl<T: Identifiable + Clone> Clone for Collection<T> {
+    fn clone(&self) -> Self {
+        Collection {
+            entries: self.entries.clone(),
+            indexes: self.indexes.clone(),
+            views: Default::default(),
+            policy: self.policy,
+            sort_comparer: self.sort_comparer
+        }
+    }
+}
+
+// entities are shown in ids order, so the output doesn't depend on hashing
+impl<T: Identifiable + Clone + Debug> Debug for Collection<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Collection").field("ids", self.entries.ids()).field("entities", &OrderedEntities(self)).finish()
+    }
+}
+
+struct OrderedEntities<'a, T: Identifiable + Clone>(&'a Collection<T>);
+
+impl<'a, T: Identifiable + Clone + Debug> Debug for OrderedEntities<'a, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.0.iter().map(|entity| (entity.get_id(), entity))).finish()
+    }
+}
+
+impl<T: Identifiable + Clone> Default for Collection<T> {
+    fn default() -> Self {
+        Collection::new()
+    }
+}
+
+impl<T: Identifiable + Clone + PartialEq> PartialEq for Collection<T> {
+    fn eq(&self, other: &Self) -> bool {
+        // the pointer check makes comparing a collection with an unchanged copy of itself cheap
+        self.entries.ptr_eq(&other.entries) || self.entries == other.entries
+    }
+}
+
+impl<T: Identifiable + Clone> Collection<T> {
+    pub fn new() -> Collection<T> {
+        Collection::with_policy(DuplicatePolicy::default())
+    }
+
+    pub fn with_policy(policy: DuplicatePolicy) -> Collection<T> {
+        Collection {
+            entries: Entries::new(),
+            indexes: HashMap::new(),
+            views: Default::default(),
+            policy,
+            sort_comparer: None
+        }
+    }
+
+    // Keeps ids sorted by comparer (equal entities in insertion order) rather than insertion order.
+    // An explicit ordering (reorder, move_to, with_id_order, retain_ids, subset) gives a plain collection again.
+    pub fn new_sorted(comparer: SortComparer<T>) -> Collection<T> {
+        Collection { sort_comparer: Some(comparer), ..Collection::new() }
+    }
+
+    // For small sequential ids (say 1..n): entities are kept in a vector indexed by id rather than
+    // hashed. Behaves exactly as new() does otherwise.
+    pub fn new_dense() -> Collection<T> where T::Id: DenseId {
+        let index_of = |id: &T::Id| id.dense_index().filter(|&index| index < DENSE_LIMIT);
+        Collection { entries: Entries::dense(index_of), ..Collection::new() }
+    }
+
+    // Owned self isn't shared with anyone, so changing it needs no clone. Only the views cached for
+    // its current contents have to go.
+    fn into_mutable(self) -> Collection<T> {
+        Collection { views: Default::default(), ..self }
+    }
+
+    // empty, with the same policy, sorting and indexes
+    fn empty_like(&self) -> Collection<T> {
+
+        let indexes = self.indexes.iter()
+            .map(|(name, index)| (*name, SecondaryIndex { key_of: index.key_of.clone(), buckets: im::HashMap::new() }))
+            .collect();
+
+        Collection { entries: self.entries.empty_like(), indexes, sort_comparer: self.sort_comparer, ..Collection::with_policy(self.policy) }
+    }
+
+    // Stores an entity, refiling it in every index. A new id goes in at position, while a replaced
+    // entity keeps its own.
+    fn put_at(&mut self, position: usize, id: T::Id, entity: T) {
+
+        let replaced = self.entries.insert(position, id.clone(), entity);
+        let entity = self.entries.get(&id).expect("an entity just stored");
+
+        for index in self.indexes.values_mut() {
+            if let Some(replaced) = &replaced {
+                index.unfile(&id, replaced);
+            }
+            index.file(&id, entity);
+        }
+    }
+
+    // a new id goes at the end
+    fn put(&mut self, id: T::Id, entity: T) {
+        self.put_at(self.entries.len(), id, entity)
+    }
+
+    // Changes an entity in place, refiling it in every index. Only this entity is copied, and only
+    // if another version of the collection still shares it. False if the id is missing.
+    fn modify(&mut self, id: &T::Id, f: impl FnOnce(&mut T)) -> bool {
+
+        // holding on to the old version makes the change copy it, so only when indexes need it
+        let previous = if self.indexes.is_empty() { None } else { self.entries.get_shared(id) };
+
+        let modified = self.entries.modify(id, |entity| {
+            f(entity);
+            debug_assert!(entity.get_id() == *id, "a modification must not change an entity's id");
+        });
+
+        if let (Some(previous), Some(entity)) = (previous, self.entries.get(id)) {
+            for index in self.indexes.values_mut() {
+                index.unfile(id, &previous);
+                index.file(id, entity);
+            }
+        }
+
+        modified
+    }
+
+    // drops an entity and unfiles it from every index
+    fn evict(&mut self, id: &T::Id) -> Option<Arc<T>> {
+
+        let evicted = self.entries.remove(id)?;
+
+        for index in self.indexes.values_mut() {
+            index.unfile(id, &evicted);
+        }
+
+        Some(evicted)
+    }
+
+    fn evict_where(&mut self, pred: impl Fn(&T::Id, &T) -> bool) {
+        for (id, evicted) in self.entries.remove_where(pred) {
+            for index in self.indexes.values_mut() {
+                index.unfile(&id, &evicted);
+            }
+        }
+    }
+
+    // Maintains an index of ids by key_of, from here on, for find_by_index. Adding an index under a
+    // name already in use replaces it.
+    pub fn with_index<K: Into<IndexKey>>(self, name: &'static str, key_of: impl Fn(&T) -> K + Send + Sync + 'static) -> Collection<T> {
+
+        let mut index = SecondaryIndex { key_of: Arc::new(move |entity: &T| key_of(entity).into()), buckets: im::HashMap::new() };
+
+        for entity in self.iter() {
+            index.file(&entity.get_id(), entity);
+        }
+
+        let mut indexed = self.into_mutable();
+        indexed.indexes.insert(name, index);
+
+        indexed
+    }
+
+    // The entities filed under key, in the order they were filed. An index that was never added finds nothing.
+    pub fn find_by_index<K: Clone + Into<IndexKey>>(&self, name: &str, key: &K) -> Vec<&T> {
+        self.indexes.get(name)
+            .and_then(|index| index.buckets.get(&key.clone().into()))
+            .map_or_else(Vec::new, |bucket| bucket.iter().filter_map(|id| self.entries.get(id)).collect())
+    }
+
+    // where a new entity's id goes in ids
+    fn position_for(&self, entity: &T) -> usize {
+        match self.sort_comparer {
+            Some(compare) => {
+                let entries = &self.entries;
+                // never Equal, so the search always ends at the first greater entity
+                let search = entries.ids().binary_search_by(|id| match entries.get(id).map(|e| compare(e, entity)) {
+                    Some(std::cmp::Ordering::Greater) => std::cmp::Ordering::Greater,
+                    _ => std::cmp::Ordering::Less,
+                });
+                search.unwrap_or_else(|position| position)
+            },
+            None => self.entries.len(),
+        }
+    }
+
+    // after entities have been replaced, as their sort keys may have changed
+    fn resort(&mut self) {
+        if let Some(compare) = self.sort_comparer {
+            self.entries.sort_by(compare);
+        }
+    }
+
+    // adds in place, following the duplicate policy
+    fn insert(&mut self, entity: &T) -> Result<(), Error<T::Id>> {
+
+        let id = entity.get_id();
+
+        if !self.entries.contains(&id) {
+            let position = self.position_for(entity);
+            self.put_at(position, id, entity.clone());
+            return Ok(());
+        }
+
+        match self.policy {
+            DuplicatePolicy::Ignore => Ok(()),
+            DuplicatePolicy::Replace => {
+                self.put(id, entity.clone());
+                self.resort();
+                Ok(())
+            },
+            DuplicatePolicy::Error => Err(Error::DuplicateId(id)),
+        }
+    }
+
+    // consumes self like every other change here, so it isn't std::ops::Add
+    #[allow(clippy::should_implement_trait)]
+    pub fn add(self, entity: &T) -> Collection<T> {
+
+        let mut new_collection = self.into_mutable();
+
+        // an Error policy's duplicate is left out, as add has no way to report it
+        let _ = new_collection.insert(entity);
+
+        debug_assert_eq!(new_collection.validate(), Ok(()));
+
+        new_collection
+    }
+
+    pub fn try_add(self, entity: &T) -> Result<Collection<T>, Error<T::Id>> {
+
+        let mut new_collection = self.into_mutable();
+
+        new_collection.insert(entity)?;
+
+        Ok(new_collection)
+    }
+
+    // an id that was never added leaves the collection as it was
+    pub fn update(self, entity: &T) -> Collection<T> {
+
+        let id = entity.get_id();
+
+        if !self.entries.contains(&id) {
+            return self;
+        }
+
+        let mut updated = self.into_mutable();
+
+        updated.put(id, entity.clone());
+        updated.resort();
+
+        debug_assert_eq!(updated.validate(), Ok(()));
+
+        updated
+    }
+
+    // mutates a single entity; a missing id leaves the collection as it was
+    pub fn map_one(self, id: &T::Id, f: impl FnOnce(&mut T)) -> Collection<T> {
+
+        if !self.entries.contains(id) {
+            return self;
+        }
+
+        let mut mapped = self.into_mutable();
+
+        mapped.modify(id, f);
+        mapped.resort();
+
+        mapped
+    }
+
+    // Applies each update in turn, in one pass. Also returns the ids that weren't found, which are
+    // skipped.
+    pub fn update_many(self, updates: &[Update<T>]) -> (Collection<T>, Vec<T::Id>) {
+
+        let mut new_collection = self.into_mutable();
+        let mut missing = vec![];
+
+        for update in updates {
+            if !new_collection.modify(&update.id, |entity| (update.changes)(entity)) {
+                missing.push(update.id.clone());
+            }
+        }
+
+        new_collection.resort();
+
+        (new_collection, missing)
+    }
+
+    // replaces in place if the id exists, keeping its position (unless sorted), and appends otherwise
+    pub fn upsert(self, entity: &T) -> Collection<T> {
+
+        if self.entries.contains(&entity.get_id()) {
+            self.update(entity)
+        } else {
+            self.add(entity)
+        }
+    }
+
+    // appended in slice order, duplicates handled as add does. Like the other *_many variants, this
+    // applies the whole batch in one pass rather than one call per entity.
+    pub fn add_many(self, entities: &[T]) -> Collection<T> {
+
+        let mut new_collection = self.into_mutable();
+
+        for entity in entities {
+            let _ = new_collection.insert(entity);
+        }
+
+        new_collection
+    }
+
+    // ids may repeat, and ids not in the collection are ignored
+    pub fn remove_many(self, ids: &[T::Id]) -> Collection<T> {
+
+        let mut new_collection = self.into_mutable();
+        let ids: HashSet<&T::Id> = ids.iter().collect();
+
+        new_collection.evict_where(|id, _| ids.contains(id));
+
+        new_collection
+    }
+
+    pub fn upsert_many(self, entities: &[T]) -> Collection<T> {
+
+        let mut new_collection = self.into_mutable();
+
+        for entity in entities {
+            new_collection.put(entity.get_id(), entity.clone());
+        }
+
+        new_collection.resort();
+
+        new_collection
+    }
+
+    // a missing id leaves the collection as it was
+    pub fn remove(self, id: &T::Id) -> Collection<T> {
+        self.take(id).0
+    }
+
+    // Removes the entity and hands it back, e.g. to undo the delete; None if the id is missing.
+    // O(log n) in the collection size: the id's position in ids is found by binary search over the
+    // order keys
+    pub fn take(self, id: &T::Id) -> (Collection<T>, Option<T>) {
+
+        if !self.entries.contains(id) {
+            return (self, None);
+        }
+
+        let mut new_collection = self.into_mutable();
+
+        // the entity is only cloned if another version of the collection still has it
+        let removed = new_collection.evict(id).map(Arc::unwrap_or_clone);
+
+        debug_assert_eq!(new_collection.validate(), Ok(()));
+
+        (new_collection, removed)
+    }
+
+    // Re-keys an entity, keeping its position (unless sorted). A missing old id, or a new id that's
+    // already taken, leaves the collection as it was.
+    pub fn change_id(self, old: &T::Id, new: T::Id) -> Collection<T> {
+
+        let position = match self.entries.position(old) {
+            Some(position) if !self.entries.contains(&new) => position,
+            _ => return self,
+        };
+
+        let mut changed = self.into_mutable();
+
+        if let Some(entity) = changed.evict(old) {
+            let mut entity = Arc::unwrap_or_clone(entity);
+            entity.set_id(new);
+            // back under the old id if it couldn't be re-keyed
+            changed.put_at(position, entity.get_id(), entity);
+        }
+
+        changed.resort();
+
+        debug_assert_eq!(changed.validate(), Ok(()));
+
+        changed
+    }
+
+    // takes the given order, unless sorted; a repeated id keeps its first position and its last value
+    pub fn set_all(self, entities: Vec<T>) -> Collection<T> {
+
+        let mut replaced = self.empty_like();
+
+        for entity in entities {
+            replaced.put(entity.get_id(), entity);
+        }
+
+        replaced.resort();
+
+        replaced
+    }
+
+    // Takes the given order, unless sorted, a repeated id being handled by the duplicate policy as
+    // add handles it: the first kept when ignoring, the last (in the first's place) when replacing
+    pub fn replace_all(self, entities: &[T]) -> Collection<T> {
+        self.empty_like().add_many(entities)
+    }
+
+    // As replace_all, failing on a repeated id under the Error policy
+    pub fn try_replace_all(self, entities: &[T]) -> Result<Collection<T>, Error<T::Id>> {
+
+        let mut replaced = self.empty_like();
+
+        for entity in entities {
+            replaced.insert(entity)?;
+        }
+
+        Ok(replaced)
+    }
+
+    pub fn clear(self) -> Collection<T> {
+        self.empty_like()
+    }
+
+    pub fn get(&self, id: &T::Id) -> Option<&T> {
+        self.entries.get(id)
+    }
+
+    pub fn contains(&self, id: &T::Id) -> bool {
+        self.entries.contains(id)
+    }
+
+    pub fn ids(&self) -> &Vector<T::Id> {
+        self.entries.ids()
+    }
+
+    pub fn policy(&self) -> DuplicatePolicy {
+        self.policy
+    }
+
+    // entities in ids order
+    pub fn iter(&self) -> Iter<'_, T> {
+        self.entries.iter()
+    }
+
+    pub fn iter_ids(&self) -> impl Iterator<Item = &T::Id> {
+        self.entries.ids().iter()
+    }
+
+    // O(1), as are is_empty and lookups by id
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.len() == 0
+    }
+
+    // folds in id order; an empty collection returns init without touching f
+    pub fn fold<B, F: Fn(B, &T) -> B>(&self, init: B, f: F) -> B {
+
+        if self.is_empty() {
+            return init;
+        }
+
+        self.iter().fold(init, f)
+    }
+
+    pub fn aggregate_field<N, F: Fn(&T) -> N>(&self, f: F) -> FieldAggregate<N>
+        where N: Copy + PartialOrd + Default + std::ops::Add<Output = N> {
+
+        let init = FieldAggregate { count: 0, sum: N::default(), min: None, max: None };
+
+        self.entries.values().map(f).fold(init, |agg, n| FieldAggregate {
+            count: agg.count + 1,
+            sum: agg.sum + n,
+            min: Some(agg.min.map_or(n, |min| if n < min { n } else { min })),
+            max: Some(agg.max.map_or(n, |max| if n > max { n } else { max })),
+        })
+    }
+
+    // The bridge to APIs wanting a slice. A real &[T] would need entities kept contiguously (with an
+    // id -> index map for lookups) rather than in a map.
+    pub fn to_ordered_refs(&self) -> Vec<&T> {
+        self.iter().collect()
+    }
+
+    // Queries in id order, borrowing rather than cloning, e.g. over store.get_state() where a
+    // Selector would take (and so clone) the whole state
+    pub fn find(&self, pred: impl Fn(&T) -> bool) -> Option<&T> {
+        self.iter().find(|entity| pred(entity))
+    }
+
+    pub fn filter(&self, pred: impl Fn(&T) -> bool) -> Vec<&T> {
+        self.iter().filter(|entity| pred(entity)).collect()
+    }
+
+    pub fn count_where(&self, pred: impl Fn(&T) -> bool) -> usize {
+        self.iter().filter(|entity| pred(entity)).count()
+    }
+
+    // first Some in id order
+    pub fn find_map<B, F: Fn(&T) -> Option<B>>(&self, f: F) -> Option<B> {
+        self.iter().find_map(f)
+    }
+
+    // pages count from 0; only the entities on the page are cloned
+    pub fn page(&self, page: usize, page_size: usize) -> Page<T> {
+
+        let total_items = self.len();
+        let total_pages = if page_size == 0 { 0 } else { total_items.div_ceil(page_size) };
+
+        let start = page.saturating_mul(page_size).min(total_items);
+        let end = start.saturating_add(page_size).min(total_items);
+        let ids = self.entries.ids();
+        let items = (start..end).filter_map(|i| self.entries.get(&ids[i])).cloned().collect();
+
+        Page { items, page, total_items, total_pages }
+    }
+
+    pub fn try_get(&self, id: &T::Id) -> Result<&T, Error<T::Id>> {
+        self.entries.get(id).ok_or_else(|| Error::NotFound(id.clone()))
+    }
+
+    // new_order must contain exactly the ids already in the collection
+    pub fn reorder(self, new_order: Vec<T::Id>) -> Result<Collection<T>, Error<T::Id>> {
+
+        let mut unplaced: HashSet<&T::Id> = self.entries.ids().iter().collect();
+
+        if new_order.len() != unplaced.len() || !new_order.iter().all(|id| unplaced.remove(id)) {
+            return Err(Error::InvalidOrder(new_order));
+        }
+
+        let mut reordered = Collection { views: Default::default(), sort_comparer: None, ..self };
+        reordered.entries.set_order(new_order.into_iter().collect());
+
+        Ok(reordered)
+    }
+
+    // e.g. for drag & drop; an index past the end moves it to the end, and a missing id does nothing
+    pub fn move_to(self, id: &T::Id, new_index: usize) -> Collection<T> {
+
+        if !self.entries.contains(id) {
+            return self;
+        }
+
+        let mut moved = Collection { views: Default::default(), sort_comparer: None, ..self };
+        moved.entries.move_to(id, new_index);
+
+        moved
+    }
+
+    // rebuilds every structure from ids, unshared with any other version of the collection
+    pub fn compact(self) -> Collection<T> {
+
+        let mut compacted = self.empty_like();
+
+        for entity in self.iter() {
+            compacted.put(entity.get_id(), entity.clone());
+        }
+
+        compacted
+    }
+
+    // Ids in both keep their position here; those only in other are appended in other's order
+    pub fn merge(self, other: &Collection<T>, policy: MergePolicy<T>) -> Collection<T> {
+
+        let mut merged = self.into_mutable();
+
+        for theirs in other.iter() {
+            let id = theirs.get_id();
+
+            let entity = match (merged.entries.get(&id), &policy) {
+                (None, _) => theirs.clone(),
+                (Some(_), MergePolicy::PreferSelf) => continue,
+                (Some(_), MergePolicy::PreferOther) => theirs.clone(),
+                (Some(ours), MergePolicy::Custom(resolve)) => resolve(ours, theirs),
+            };
+
+            debug_assert!(entity.get_id() == id, "a merge policy must not change an entity's id");
+            merged.put(id, entity);
+        }
+
+        merged.resort();
+
+        merged
+    }
+
+    // Checks ids and entities are in step, which Entries guarantees. Kept as a cheap debug check on
+    // Entries itself.
+    pub fn validate(&self) -> Result<(), CollectionInvariantError<T::Id>> {
+        self.entries.validate()
+    }
+
+    #[cfg(test)]
+    fn out_of_step(ids: Vec<T::Id>, entities: Vec<T>) -> Collection<T> {
+        Collection { entries: Entries::out_of_step(ids, entities), ..Collection::new() }
+    }
+
+    // Fixes whatever validate finds. Only a bug in Entries could leave anything to fix, as add no
+    // longer lets duplicate ids through.
+    pub fn repair(self) -> (Collection<T>, RepairReport<T::Id>) {
+
+        let mut repaired = self.empty_like();
+        let (entries, report) = self.entries.repair();
+
+        for entity in entries.iter() {
+            repaired.put(entity.get_id(), entity.clone());
+        }
+
+        (repaired, report)
+    }
+
+    // keeps only the given ids, in the given order; ids not in the collection are ignored
+    pub fn retain_ids(self, ids: &[T::Id]) -> Collection<T> {
+        self.subset(ids)
+    }
+
+    // keeps the entities matching pred, in their order. As an action, that's a RemoveMany of the
+    // ids a selector finds not matching.
+    pub fn retain(self, pred: impl Fn(&T) -> bool) -> Collection<T> {
+
+        let mut retained = self.into_mutable();
+
+        retained.evict_where(|_, entity| !pred(entity));
+
+        retained
+    }
+
+    // None drops the entity, Some replaces it; replacements must keep their id
+    pub fn retain_map<F: Fn(&T) -> Option<T>>(self, f: F) -> Collection<T> {
+
+        let mut retained = self.empty_like();
+
+        for (id, entity) in self.iter().filter_map(|entity| Some((entity.get_id(), f(entity)?))) {
+            debug_assert_eq!(entity.get_id(), id, "retain_map must not change an entity's id");
+            retained.put(id, entity);
+        }
+
+        retained.resort();
+
+        retained
+    }
+
+    // ids listed in order come first, in that order; the rest keep their relative order at the end
+    pub fn with_id_order(self, order: &[T::Id]) -> Collection<T> {
+
+        let mut placed: HashSet<&T::Id> = HashSet::new();
+
+        let ordered = order.iter().filter(|id| self.entries.contains(id));
+        let ids: Vector<T::Id> = ordered.chain(self.entries.ids().iter())
+            .filter(|id| placed.insert(id))
+            .cloned()
+            .collect();
+
+        let mut ordered = Collection { views: Default::default(), sort_comparer: None, ..self };
+        ordered.entries.set_order(ids);
+
+        ordered
+    }
+
+    // a detached copy of just the given ids (in that order), e.g. to edit in isolation
+    pub fn subset(&self, ids: &[T::Id]) -> Collection<T> {
+
+        let mut subset = Collection { sort_comparer: None, ..self.empty_like() };
+
+        for id in ids {
+            if subset.entries.contains(id) {
+                continue;
+            }
+            if let Some(entity) = self.entries.get(id) {
+                subset.put(id.clone(), entity.clone());
+            }
+        }
+
+        subset
+    }
+
+    // Computes the view under key once and shares it with every later caller while any of them still
+    // holds it. A key must always describe the same view (and type) of the collection. The cache isn't
+    // locked while computing, so compute can build on other views; should another caller get there
+    // first, theirs is the one shared.
+    pub fn view<V, F>(&self, key: &'static str, compute: F) -> Arc<V>
+        where V: Any + Send + Sync, F: FnOnce(&Self) -> V {
+
+        if let Some(view) = self.cached_view(key) {
+            return view;
+        }
+
+        let view = Arc::new(compute(self));
+
+        let mut views = self.views.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        if let Some(view) = views.get(key).and_then(Weak::upgrade).and_then(|view| view.downcast::<V>().ok()) {
+            return view;
+        }
+
+        let shared: Arc<dyn Any + Send + Sync> = view.clone();
+
+        views.retain(|_, cached| cached.strong_count() > 0);
+        views.insert(key, Arc::downgrade(&shared));
+
+        view
+    }
+
+    fn cached_view<V: Any + Send + Sync>(&self, key: &'static str) -> Option<Arc<V>> {
+        let views = self.views.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        views.get(key).and_then(Weak::upgrade).and_then(|view| view.downcast::<V>().ok())
+    }
+
+    #[cfg(feature = "rayon")]
+    pub fn par_count_where<P: Fn(&T) -> bool + Sync>(&self, pred: P) -> usize where T: Send + Sync, T::Id: Send + Sync {
+        self.entries.ids().par_iter().filter_map(|id| self.entries.get(id)).filter(|entity| pred(entity)).count()
+    }
+
+    // same result (and order) as a sequential filter over ids
+    #[cfg(feature = "rayon")]
+    pub fn par_filter<P: Fn(&T) -> bool + Sync>(&self, pred: P) -> Vec<&T> where T: Send + Sync, T::Id: Send + Sync {
+        self.entries.ids().par_iter().filter_map(|id| self.entries.get(id)).filter(|entity| pred(entity)).collect()
+    }
+
+    // A rough figure: tree nodes are approximated, not measured, and structure shared with other
+    // versions of the collection is counted as if this one owned it
+    #[cfg(feature = "heap-size")]
+    pub fn estimated_heap_size(&self) -> usize where T: HeapSize {
+
+        let ids = self.len() * std::mem::size_of::<T::Id>();
+        let buckets = self.len() * (std::mem::size_of::<(T::Id, Arc<T>)>() + std::mem::size_of::<usize>());
+        // each Arc's allocation holds two counts alongside the entity
+        let arcs = self.len() * (std::mem::size_of::<T>() + 2 * std::mem::size_of::<usize>());
+        let entities: usize = self.entries.values().map(|entity| entity.heap_size()).sum();
+
+        ids + buckets + arcs + entities
+    }
+}
+
+pub struct Iter<'a, T: Identifiable + Clone> {
+    ids: im::vector::Iter<'a, T::Id>,
+    entries: &'a Entries<T>,
+}
+
+impl<'a, T: Identifiable + Clone> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let entries = self.entries;
+        self.ids.find_map(|id| entries.get(id))
+    }
+}
+
+impl<'a, T: Identifiable + Clone> IntoIterator for &'a Collection<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+// Duplicate ids are handled by the duplicate policy, as add does. Collecting gives the default
+// policy, so the first entity with an id wins.
+impl<T: Identifiable + Clone> Extend<T> for Collection<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, entities: I) {
+
+        self.views = Default::default();
+
+        for entity in entities {
+            let _ = self.insert(&entity);
+        }
+    }
+}
+
+impl<T: Identifiable + Clone> FromIterator<T> for Collection<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(entities: I) -> Self {
+        let mut collection = Collection::new();
+        collection.extend(entities);
+        collection
+    }
+}
+
+impl<T: Identifiable + Clone + PartialEq> Diffable for Collection<T> {
+    type Diff = CollectionDiff<T::Id>;
+
+    fn diff(&self, prev: &Self) -> CollectionDiff<T::Id> {
+
+        let mut diff = CollectionDiff { added: vec![], removed: vec![], changed: vec![], moved: vec![] };
+
+        for entity in self.iter() {
+            let id = entity.get_id();
+            match prev.get(&id) {
+                None => diff.added.push(id),
+                Some(old) if entity != old => diff.changed.push(id),
+                Some(_) => {},
+            }
+        }
+
+        diff.removed = prev.iter_ids().filter(|id| !self.contains(id)).cloned().collect();
+
+        // ids in both, in current order, with their previous positions among them. Those in the
+        // longest run of still increasing positions stayed put; the rest moved around them.
+        let previous: HashMap<&T::Id, usize> = prev.iter_ids().filter(|id| self.contains(id)).enumerate().map(|(i, id)| (id, i)).collect();
+        let kept: Vec<(&T::Id, usize)> = self.iter_ids().filter_map(|id| previous.get(id).map(|&i| (id, i))).collect();
+        let stayed = longest_increasing(&kept.iter().map(|&(_, i)| i).collect::<Vec<_>>());
+
+        diff.moved = kept.iter().enumerate().filter(|(k, _)| !stayed.contains(k)).map(|(_, &(id, _))| id.clone()).collect();
+
+        diff
+    }
+}
+
+// indices of a longest strictly increasing subsequence of values, in O(n log n)
+fn longest_increasing(values: &[usize]) -> HashSet<usize> {
+
+    // tails[k] is the index of the smallest value ending an increasing run of length k + 1
+    let mut tails: Vec<usize> = vec![];
+    let mut before = vec![None; values.len()];
+
+    for (i, &value) in values.iter().enumerate() {
+        let k = tails.partition_point(|&t| values[t] < value);
+        before[i] = k.checked_sub(1).map(|k| tails[k]);
+        if k == tails.len() {
+            tails.push(i);
+        } else {
+            tails[k] = i;
+        }
+    }
+
+    let mut run = HashSet::new();
+    let mut next = tails.last().copied();
+
+    while let Some(i) = next {
+        run.insert(i);
+        next = before[i];
+    }
+
+    run
+}
+
+// Serialized as a sequence of entities in id order. Policy and sorting aren't part of it, so a
+// deserialized collection has the defaults.
+#[cfg(feature = "serde")]
+impl<T: Identifiable + Clone + Serialize> Serialize for Collection<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: Identifiable + Clone + Deserialize<'de>> Deserialize<'de> for Collection<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+
+        let mut collection = Collection::new();
+
+        for entity in Vec::<T>::deserialize(deserializer)? {
+            let id = entity.get_id();
+            if collection.contains(&id) {
+                return Err(D::Error::custom(format!("duplicate id {:?} in collection", id)));
+            }
+            collection.put(id, entity);
+        }
+
+        Ok(collection)
+    }
+}
+
+// Selectors for Store::select (or observe), given where the collection lives in the state, e.g.
+// store.select(Collection::select_total(|state: &RootState| state.todos.clone()))
+impl<T: Identifiable + Clone + 'static> Collection<T> {
+    pub fn select_all<State>(collection: impl Fn(&State) -> Collection<T> + 'static) -> Box<Selector<State, Vec<T>>> {
+        Box::new(move |state| collection(state).iter().cloned().collect())
+    }
+
+    pub fn select_ids<State>(collection: impl Fn(&State) -> Collection<T> + 'static) -> Box<Selector<State, Vec<T::Id>>> {
+        Box::new(move |state| collection(state).iter_ids().cloned().collect())
+    }
+
+    pub fn select_entities<State>(collection: impl Fn(&State) -> Collection<T> + 'static) -> Box<Selector<State, HashMap<T::Id, T>>> {
+        Box::new(move |state| collection(state).iter().map(|entity| (entity.get_id(), entity.clone())).collect())
+    }
+
+    pub fn select_total<State>(collection: impl Fn(&State) -> Collection<T> + 'static) -> Box<Selector<State, usize>> {
+        Box::new(move |state| collection(state).len())
+    }
+
+    pub fn select_page<State>(collection: impl Fn(&State) -> Collection<T> + 'static, page: usize, page_size: usize) -> Box<Selector<State, Page<T>>> {
+        Box::new(move |state| collection(state).page(page, page_size))
+    }
+
+    pub fn select_by_id<State>(collection: impl Fn(&State) -> Collection<T> + 'static, id: T::Id) -> Box<Selector<State, Option<T>>>
+        where T::Id: 'static {
+        Box::new(move |state| collection(state).get(&id).cloned())
+    }
+}
+
+impl<State: Clone + 'static, Action: Clone + 'static, E> Store<State, Action, E> {
+    // Calls on_change with the collection's length whenever a dispatch changes it, e.g. to show an
+    // empty list placeholder. Changes leaving the length as it was, say to a field, aren't reported.
+    pub fn on_size_change<T: Identifiable + Clone + 'static>(&mut self, collection: impl Fn(&State) -> Collection<T> + 'static, on_change: impl Fn(usize) + 'static) -> SubscriptionHandle {
+        let last = Cell::new(collection(&self.get_state()).len());
+        self.observe(move |state| collection(state).len(), move |&len| {
+            if last.replace(len) != len {
+                on_change(len);
+            }
+        })
+    }
+}
+
+// A partial change to one entity. changes is shared so the actions carrying updates stay Clone, and
+// Send + Sync so they can be dispatched to a SharedStore.
+pub struct Update<T: Identifiable> {
+    id: T::Id,
+    changes: Arc<dyn Fn(&mut T) + Send + Sync>,
+}
+
+impl<T: Identifiable> Update<T> {
+    pub fn new(id: T::Id, changes: impl Fn(&mut T) + Send + Sync + 'static) -> Update<T> {
+        Update { id, changes: Arc::new(changes) }
+    }
+}
+
+impl<T: Identifiable> Clone for Update<T> {
+    fn clone(&self) -> Self {
+        Update { id: self.id.clone(), changes: self.changes.clone() }
+    }
+}
+
+impl<T: Identifiable> Debug for Update<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Update").field("id", &self.id).finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EntityAction;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Item {
+        id: i32,
+        name: &'static str,
+    }
+
+    impl Identifiable for Item {
+        type Id = i32;
+
+        fn get_id(&self) -> i32 {
+            self.id
+        }
+
+        fn set_id(&mut self, id: i32) {
+            self.id = id;
+        }
+    }
+
+    fn item(id: i32, name: &'static str) -> Item {
+        Item { id, name }
+    }
+
+    fn ids(items: &Collection<Item>) -> Vec<i32> {
+        items.ids().iter().cloned().collect()
+    }
+
+    #[test]
+    fn keeps_its_order_through_inserts_moves_and_removals() {
+        // each new item goes in first, so there's soon no room left between keys
+        let mut items = Collection::new_sorted(|a: &Item, b: &Item| b.id.cmp(&a.id));
+        for id in 0..100 {
+            items = items.add(&item(id, "item"));
+        }
+        assert_eq!(ids(&items), (0..100).rev().collect::<Vec<_>>());
+        assert_eq!(items.validate(), Ok(()));
+
+        let items = items.remove(&50).move_to(&0, 0).move_to(&99, 200).change_id(&98, 1000);
+        let mut expected: Vec<i32> = (1..98).rev().filter(|id| *id != 50).collect();
+        expected.insert(0, 0);
+        expected.insert(1, 1000);
+        expected.push(99);
+
+        assert_eq!(ids(&items), expected);
+        assert_eq!(items.validate(), Ok(()));
+    }
+
+    #[test]
+    fn replacing_everything_follows_the_duplicate_policy() {
+        let incoming = [item(2, "two"), item(1, "one"), item(2, "again")];
+        let names = |items: &Collection<Item>| items.iter().map(|item| item.name).collect::<Vec<_>>();
+
+        let ignored = Collection::new().add(&item(3, "three")).replace_all(&incoming);
+        assert_eq!(names(&ignored), vec!["two", "one"]);
+
+        let replaced = Collection::with_policy(DuplicatePolicy::Replace).replace_all(&incoming);
+        assert_eq!(names(&replaced), vec!["again", "one"]);
+
+        let refused = Collection::with_policy(DuplicatePolicy::Error).try_replace_all(&incoming);
+        assert!(matches!(refused, Err(Error::DuplicateId(2))));
+    }
+
+    #[test]
+    fn reordering_takes_a_permutation_of_the_ids() {
+        let items = Collection::new().add_many(&[item(1, "one"), item(2, "two"), item(3, "three"), item(4, "four")]);
+
+        let reordered = items.clone().reorder(vec![3, 1, 4, 2]).unwrap();
+        assert_eq!(ids(&reordered), vec![3, 1, 4, 2]);
+        assert_eq!(reordered.get(&4), Some(&item(4, "four")));
+
+        assert!(matches!(items.clone().reorder(vec![3, 1, 4]), Err(Error::InvalidOrder(_))));
+        assert!(matches!(items.clone().reorder(vec![3, 1, 4, 4]), Err(Error::InvalidOrder(_))));
+        assert!(matches!(items.reorder(vec![3, 1, 4, 5]), Err(Error::InvalidOrder(_))));
+    }
+
+    #[test]
+    fn an_external_order_puts_the_rest_at_the_end() {
+        let items = Collection::new().add_many(&[item(1, "one"), item(2, "two"), item(3, "three"), item(4, "four")]);
+
+        // 9 isn't in the collection, and 3 is only placed once
+        let ordered = items.with_id_order(&[3, 9, 1, 3]);
+
+        assert_eq!(ids(&ordered), vec![3, 1, 2, 4]);
+        assert_eq!(ordered.validate(), Ok(()));
+    }
+
+    #[test]
+    fn changing_an_id_keeps_the_entity_in_place() {
+        let items = Collection::new().add_many(&[item(1, "one"), item(2, "two"), item(3, "three")]);
+
+        let items = items.change_id(&2, 20);
+
+        assert_eq!(ids(&items), vec![1, 20, 3]);
+        assert_eq!(items.get(&20), Some(&item(20, "two")));
+        assert!(items.get(&2).is_none());
+        // onto an id that's taken, nothing changes
+        assert_eq!(ids(&items.change_id(&1, 3)), vec![1, 20, 3]);
+    }
+
+    #[test]
+    fn views_can_build_on_other_views() {
+        let items = Collection::new().add_many(&[item(1, "one"), item(2, "two"), item(3, "three")]);
+
+        let names = items.view("names", |items| items.iter().map(|item| item.name).collect::<Vec<_>>());
+        let long_names = items.view("long names", |items| {
+            let names = items.view("names", |items| items.iter().map(|item| item.name).collect::<Vec<_>>());
+            names.iter().filter(|name| name.len() > 3).count()
+        });
+
+        assert_eq!(*long_names, 1);
+        assert!(Arc::ptr_eq(&names, &items.view("names", |_| vec![])));
+    }
+
+    #[test]
+    fn size_changes_are_reported_but_other_changes_arent() {
+        let mut store = Store::new(Collection::new().add(&item(1, "one")));
+        store.register_reducer(Box::new(crate::entity_reducer));
+        let sizes = Arc::new(Mutex::new(vec![]));
+        let reported = sizes.clone();
+        store.on_size_change(|items: &Collection<Item>| items.clone(), move |len| reported.lock().unwrap().push(len));
+
+        store.dispatch(EntityAction::ReplaceEntity(item(1, "renamed"))).unwrap();
+        store.dispatch(EntityAction::AddEntity(item(2, "two"))).unwrap();
+        store.dispatch(EntityAction::ReplaceEntity(item(2, "renamed"))).unwrap();
+        store.dispatch(EntityAction::RemoveEntity(1)).unwrap();
+
+        assert_eq!(*sizes.lock().unwrap(), vec![2, 1]);
+    }
+
+    #[test]
+    fn retaining_ids_keeps_those_listed_in_their_order() {
+        let items = Collection::new().add_many(&[item(1, "one"), item(2, "two"), item(3, "three"), item(4, "four")]);
+
+        // 9 isn't in the collection, so it's ignored
+        let retained = items.retain_ids(&[4, 9, 2]);
+
+        assert_eq!(ids(&retained), vec![4, 2]);
+        assert_eq!(retained.iter().cloned().collect::<Vec<_>>(), vec![item(4, "four"), item(2, "two")]);
+        assert_eq!(retained.validate(), Ok(()));
+    }
+
+    #[cfg(feature = "heap-size")]
+    impl HeapSize for Item {
+        fn heap_size(&self) -> usize {
+            0
+        }
+    }
+
+    #[cfg(feature = "heap-size")]
+    #[test]
+    fn the_heap_size_estimate_grows_with_the_collection() {
+        let items = Collection::new().add(&item(1, "one"));
+        let before = items.estimated_heap_size();
+
+        let items = items.add_many(&[item(2, "two"), item(3, "three")]);
+
+        assert!(items.estimated_heap_size() > before);
+        assert_eq!(Collection::<Item>::new().estimated_heap_size(), 0);
+    }
+
+    #[test]
+    fn a_subset_can_be_edited_and_merged_back() {
+        let items = Collection::new().add_many(&[item(1, "one"), item(2, "two"), item(3, "three")]);
+
+        let subset = items.subset(&[3, 1]);
+        assert_eq!(ids(&subset), vec![3, 1]);
+
+        let edited = subset.update(&item(3, "edited"));
+        let merged = items.merge(&edited, MergePolicy::PreferOther);
+
+        assert_eq!(ids(&merged), vec![1, 2, 3]);
+        assert_eq!(merged.get(&3), Some(&item(3, "edited")));
+        assert_eq!(merged.get(&1), Some(&item(1, "one")));
+    }
+
+    #[test]
+    fn try_get_reports_a_missing_id() {
+        let items = Collection::new().add(&item(1, "one"));
+
+        assert_eq!(items.try_get(&1).ok(), Some(&item(1, "one")));
+        assert!(matches!(items.try_get(&2), Err(Error::NotFound(2))));
+    }
+
+    #[test]
+    fn folding_nothing_skips_the_closure() {
+        let calls = Cell::new(0);
+        let count = |total: usize, _: &Item| {
+            calls.set(calls.get() + 1);
+            total + 1
+        };
+
+        assert_eq!(Collection::<Item>::new().fold(0, count), 0);
+        assert_eq!(calls.get(), 0);
+
+        assert_eq!(Collection::new().add_many(&[item(1, "one"), item(2, "two")]).fold(0, count), 2);
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_queries_match_sequential_ones() {
+        let items: Collection<Item> = (0..10_000).map(|id| item(id, if id % 3 == 0 { "fizz" } else { "item" })).collect();
+        let fizz = |item: &Item| item.name == "fizz";
+
+        assert_eq!(items.par_count_where(fizz), items.count_where(fizz));
+        assert_eq!(items.par_filter(fizz), items.filter(fizz));
+    }
+
+    #[test]
+    fn find_map_extracts_from_the_first_match_in_order() {
+        let items = Collection::new().add_many(&[item(3, "three"), item(1, "one"), item(2, "two")]);
+        let short_name = |item: &Item| if item.name.len() == 3 { Some(item.name) } else { None };
+
+        assert_eq!(items.find_map(short_name), Some("one"));
+        assert_eq!(items.remove(&1).remove(&2).find_map(short_name), None);
+    }
+
+    #[test]
+    fn retain_map_keeps_and_changes_in_one_go() {
+        let items = Collection::new().add_many(&[item(1, "one"), item(2, "two"), item(3, "three"), item(4, "four")]);
+
+        let kept = items.retain_map(|item| if item.id % 2 == 0 { Some(Item { name: "even", ..*item }) } else { None });
+
+        assert_eq!(kept.iter().cloned().collect::<Vec<_>>(), vec![item(2, "even"), item(4, "even")]);
+        assert_eq!(kept.validate(), Ok(()));
+    }
+
+    #[test]
+    fn repairing_keeps_ids_and_entities_in_step() {
+        let entities = vec![item(1, "one"), item(2, "two"), item(3, "three")];
+        let broken = Collection::out_of_step(vec![1, 2, 2, 4], entities);
+        assert_eq!(broken.validate(), Err(CollectionInvariantError::DuplicateId(2)));
+
+        let (repaired, report) = broken.repair();
+
+        assert_eq!(report, RepairReport { duplicate_ids: vec![2], missing_entities: vec![4], orphaned_entities: vec![3] });
+        assert_eq!(ids(&repaired), vec![1, 2]);
+        assert_eq!(repaired.validate(), Ok(()));
+        assert!(repaired.repair().1.is_clean());
+    }
+
+    #[test]
+    fn aggregates_a_field_in_one_pass() {
+        let items = Collection::new().add_many(&[item(4, "four"), item(1, "one"), item(7, "seven")]);
+
+        let ids = items.aggregate_field(|item| item.id);
+        assert_eq!((ids.count, ids.sum, ids.min, ids.max), (3, 12, Some(1), Some(7)));
+
+        let none = Collection::<Item>::new().aggregate_field(|item| item.id);
+        assert_eq!((none.count, none.sum, none.min, none.max), (0, 0, None, None));
+    }
+
+    #[test]
+    fn ordered_refs_match_iteration() {
+        let items = Collection::new().add_many(&[item(3, "three"), item(1, "one"), item(2, "two")]).move_to(&2, 0);
+
+        assert_eq!(items.to_ordered_refs(), items.iter().collect::<Vec<_>>());
+        assert_eq!(items.to_ordered_refs()[0], &item(2, "two"));
+    }
+
+    #[test]
+    fn iterates_in_ids_order() {
+        let items = Collection::new()
+            .add_many(&[item(5, "five"), item(1, "one"), item(3, "three"), item(2, "two")])
+            .remove(&1)
+            .update(&item(3, "replaced"));
+
+        assert_eq!(ids(&items), vec![5, 3, 2]);
+        assert_eq!(items.iter_ids().cloned().collect::<Vec<_>>(), vec![5, 3, 2]);
+        assert_eq!((&items).into_iter().map(|item| item.name).collect::<Vec<_>>(), vec!["five", "replaced", "two"]);
+    }
+
+    #[test]
+    fn map_one_changes_just_that_entity() {
+        let items = Collection::new().add_many(&[item(1, "one"), item(2, "two")]);
+        let other = items.get(&2).map(|item| item as *const Item);
+
+        let mapped = items.clone().map_one(&1, |item| item.name = "changed");
+        assert_eq!(mapped.get(&1), Some(&item(1, "changed")));
+        // shared, not copied
+        assert_eq!(mapped.get(&2).map(|item| item as *const Item), other);
+        assert_eq!(items.get(&1), Some(&item(1, "one")));
+
+        let missing = items.clone().map_one(&3, |item| item.name = "changed");
+        assert_eq!(missing, items);
+    }
+
+    #[test]
+    fn update_many_reports_the_ids_it_couldnt_find() {
+        let items = Collection::new().add_many(&[item(1, "one"), item(2, "two"), item(3, "three")]);
+        let rename = |id| Update::new(id, |item: &mut Item| item.name = "renamed");
+
+        let (updated, missing) = items.update_many(&[rename(1), rename(7), rename(3), rename(9)]);
+
+        assert_eq!(missing, vec![7, 9]);
+        assert_eq!(updated.iter().map(|item| item.name).collect::<Vec<_>>(), vec!["renamed", "two", "renamed"]);
+    }
+
+    #[test]
+    fn selectors_read_a_collection_from_the_store() {
+        #[derive(Clone)]
+        struct State {
+            items: Collection<Item>,
+        }
+
+        let mut store = Store::new(State { items: Collection::new() });
+        store.register_reducer(Box::new(|state: &State, action| State { items: crate::entity_reducer(&state.items, action) }));
+        store.dispatch(EntityAction::AddMany(vec![item(2, "two"), item(1, "one")])).unwrap();
+        store.dispatch(EntityAction::AddEntity(item(3, "three"))).unwrap();
+
+        let items = |state: &State| state.items.clone();
+        assert_eq!(store.select(Collection::select_all(items)), vec![item(2, "two"), item(1, "one"), item(3, "three")]);
+        assert_eq!(store.select(Collection::select_ids(items)), vec![2, 1, 3]);
+        assert_eq!(store.select(Collection::select_entities(items)).get(&1), Some(&item(1, "one")));
+        assert_eq!(store.select(Collection::select_total(items)), 3);
+        assert_eq!(store.select(Collection::select_by_id(items, 3)), Some(item(3, "three")));
+        assert_eq!(store.select(Collection::select_by_id(items, 4)), None);
+    }
+
+    #[test]
+    fn move_to_puts_an_id_where_asked() {
+        let items = Collection::new().add_many(&[item(1, "one"), item(2, "two"), item(3, "three"), item(4, "four")]);
+
+        assert_eq!(ids(&items.clone().move_to(&3, 0)), vec![3, 1, 2, 4]);
+        assert_eq!(ids(&items.clone().move_to(&2, 3)), vec![1, 3, 4, 2]);
+        assert_eq!(ids(&items.clone().move_to(&1, 100)), vec![2, 3, 4, 1]);
+        assert_eq!(ids(&items.move_to(&5, 0)), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn retain_keeps_ids_and_entities_in_step() {
+        let items = Collection::new().add_many(&[item(1, "one"), item(2, "two"), item(3, "three"), item(4, "four")]);
+
+        let kept = items.retain(|item| item.name.len() == 3);
+
+        assert_eq!(ids(&kept), vec![1, 2]);
+        assert!(!kept.contains(&3) && !kept.contains(&4));
+        assert_eq!(kept.validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_names_what_is_out_of_step() {
+        let entities = || vec![item(1, "one"), item(2, "two")];
+
+        assert_eq!(Collection::out_of_step(vec![1, 2, 3], entities()).validate(), Err(CollectionInvariantError::MissingEntity(3)));
+        assert_eq!(Collection::out_of_step(vec![1, 2, 1], entities()).validate(), Err(CollectionInvariantError::DuplicateId(1)));
+        assert_eq!(Collection::out_of_step(vec![1], entities()).validate(), Err(CollectionInvariantError::OrphanedEntity(2)));
+        assert_eq!(Collection::out_of_step(vec![2, 1], entities()).validate(), Err(CollectionInvariantError::MisplacedId(1)));
+        assert_eq!(Collection::out_of_step(vec![1, 2], entities()).validate(), Ok(()));
+    }
+
+    #[test]
+    fn merging_follows_the_policy_for_shared_ids() {
+        let ours = Collection::new().add_many(&[item(1, "ours"), item(2, "ours")]);
+        let names = |items: &Collection<Item>| items.iter().map(|item| (item.id, item.name)).collect::<Vec<_>>();
+
+        let disjoint = ours.clone().merge(&Collection::new().add(&item(3, "theirs")), MergePolicy::PreferSelf);
+        assert_eq!(names(&disjoint), vec![(1, "ours"), (2, "ours"), (3, "theirs")]);
+
+        let theirs = Collection::new().add_many(&[item(2, "theirs"), item(1, "theirs")]);
+        assert_eq!(names(&ours.clone().merge(&theirs, MergePolicy::PreferSelf)), vec![(1, "ours"), (2, "ours")]);
+        assert_eq!(names(&ours.clone().merge(&theirs, MergePolicy::PreferOther)), vec![(1, "theirs"), (2, "theirs")]);
+
+        let custom = ours.merge(&theirs, MergePolicy::Custom(|ours, _| Item { name: "both", ..*ours }));
+        assert_eq!(names(&custom), vec![(1, "both"), (2, "both")]);
+        assert_eq!(custom.validate(), Ok(()));
+    }
+
+    #[test]
+    fn indexes_follow_updates() {
+        let items = Collection::new()
+            .with_index("done", |item: &Item| item.name == "done")
+            .add_many(&[item(1, "todo"), item(2, "done"), item(3, "todo")]);
+        let done = |items: &Collection<Item>, done: bool| {
+            let mut ids: Vec<i32> = items.find_by_index("done", &done).iter().map(|item| item.id).collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(done(&items, true), vec![2]);
+
+        let items = items.update(&item(1, "done")).map_one(&2, |item| item.name = "todo");
+
+        assert_eq!(done(&items, true), vec![1]);
+        assert_eq!(done(&items, false), vec![2, 3]);
+        assert_eq!(done(&items.remove(&1), true), Vec::<i32>::new());
+    }
+
+    #[test]
+    fn pages_through_a_collection() {
+        let items: Collection<Item> = (1..=25).map(|id| item(id, "item")).collect();
+        let page_ids = |page: usize| items.page(page, 10).items.iter().map(|item| item.id).collect::<Vec<_>>();
+
+        assert_eq!(page_ids(0), (1..=10).collect::<Vec<_>>());
+        assert_eq!(page_ids(1), (11..=20).collect::<Vec<_>>());
+        assert_eq!(page_ids(2), (21..=25).collect::<Vec<_>>());
+        assert!(page_ids(3).is_empty());
+
+        let last = items.page(2, 10);
+        assert_eq!((last.page, last.total_items, last.total_pages), (2, 25, 3));
+    }
+
+    // counts its clones in the counter it shares with the others
+    #[derive(Debug)]
+    struct Counted {
+        id: i32,
+        done: bool,
+        clones: Arc<Mutex<usize>>,
+    }
+
+    impl Clone for Counted {
+        fn clone(&self) -> Self {
+            *self.clones.lock().unwrap() += 1;
+            Counted { id: self.id, done: self.done, clones: self.clones.clone() }
+        }
+    }
+
+    impl Identifiable for Counted {
+        type Id = i32;
+
+        fn get_id(&self) -> i32 {
+            self.id
+        }
+    }
+
+    #[test]
+    fn queries_dont_clone_entities() {
+        let clones = Arc::new(Mutex::new(0));
+        let items: Collection<Counted> = [3, 1, 4, 2].iter().map(|&id| Counted { id, done: false, clones: clones.clone() }).collect();
+        *clones.lock().unwrap() = 0;
+
+        let even = |item: &Counted| item.id % 2 == 0;
+        assert_eq!(items.filter(even).iter().map(|item| item.id).collect::<Vec<_>>(), vec![4, 2]);
+        assert_eq!(items.find(even).map(|item| item.id), Some(4));
+        assert_eq!(items.count_where(even), 2);
+
+        assert_eq!(*clones.lock().unwrap(), 0);
+    }
+
+    #[test]
+    fn diffs_tell_a_change_from_a_move() {
+        let before = Collection::new().add_many(&[item(1, "one"), item(2, "two"), item(3, "three"), item(4, "four")]);
+
+        let changed = before.clone().update(&item(2, "changed"));
+        assert_eq!(changed.diff(&before), CollectionDiff { added: vec![], removed: vec![], changed: vec![2], moved: vec![] });
+
+        let moved = before.clone().move_to(&1, 3);
+        assert_eq!(moved.diff(&before), CollectionDiff { added: vec![], removed: vec![], changed: vec![], moved: vec![1] });
+
+        assert!(before.diff(&before.clone()).is_empty());
+        assert_eq!(moved, before.move_to(&1, 3));
+    }
+
+
+    // every backend has to get the same answers from the same steps
+    fn behaves_like_a_collection(empty: fn() -> Collection<Item>) {
+        let items = empty().add_many(&[item(3, "three"), item(1, "one"), item(2, "two")]);
+        assert_eq!(ids(&items), vec![3, 1, 2]);
+        assert_eq!(items.get(&1), Some(&item(1, "one")));
+        assert_eq!(items.get(&4), None);
+        assert_eq!(items.len(), 3);
+
+        let items = items.add(&item(1, "again")).update(&item(2, "changed")).upsert(&item(5, "five"));
+        assert_eq!(items.get(&1).unwrap().name, "one");
+        assert_eq!(items.get(&2).unwrap().name, "changed");
+        assert_eq!(ids(&items), vec![3, 1, 2, 5]);
+
+        let items = items.remove(&1).move_to(&5, 0).change_id(&3, 4);
+        assert_eq!(ids(&items), vec![5, 4, 2]);
+        assert_eq!(items.get(&4), Some(&item(4, "three")));
+        assert!(!items.contains(&3));
+        assert!(items.validate().is_ok());
+
+        // ids too large or negative to index by
+        let items = items.add(&item(-1, "negative")).add(&item(i32::MAX, "huge"));
+        assert_eq!(items.get(&-1).unwrap().name, "negative");
+        assert_eq!(items.get(&i32::MAX).unwrap().name, "huge");
+        assert_eq!(ids(&items), vec![5, 4, 2, -1, i32::MAX]);
+        let items = items.remove(&-1).remove(&i32::MAX);
+        assert_eq!(ids(&items), vec![5, 4, 2]);
+
+        let actions = [
+            EntityAction::AddMany(vec![item(7, "seven"), item(6, "six")]),
+            EntityAction::RemoveEntity(5),
+            EntityAction::ReplaceEntity(item(4, "four")),
+            EntityAction::MoveEntity(6, 0),
+        ];
+        let items = actions.iter().fold(items, |items, action| crate::entity_reducer(&items, action));
+        assert_eq!(ids(&items), vec![6, 4, 2, 7]);
+        assert_eq!(items.iter().map(|item| item.name).collect::<Vec<_>>(), vec!["six", "four", "changed", "seven"]);
+
+        assert!(crate::entity_reducer(&items, &EntityAction::RemoveAll).is_empty());
+    }
+
+    #[test]
+    fn a_hashed_collection_behaves_like_a_collection() {
+        behaves_like_a_collection(Collection::new);
+    }
+
+    #[test]
+    fn a_dense_collection_behaves_like_a_collection() {
+        behaves_like_a_collection(Collection::new_dense);
+    }
+
+
+    #[test]
+    fn compacting_keeps_lookups_and_order() {
+        let names = ["zero", "one", "two", "three", "four"];
+        let items: Collection<Item> = (0..1000).map(|id| item(id, names[id as usize % 5])).collect();
+        let removed: Vec<i32> = (0..1000).filter(|id| id % 3 == 0).collect();
+        let items = (0..50).fold(items.remove_many(&removed), |items, n| items.move_to(&(n * 10 + 1), n as usize * 7));
+
+        let compacted = items.clone().compact();
+
+        assert_eq!(compacted.validate(), Ok(()));
+        assert_eq!(compacted.len(), items.len());
+        assert!(compacted.iter().eq(items.iter()));
+        assert!((0..1000).all(|id| compacted.get(&id) == items.get(&id)));
+        assert_eq!(compacted, items);
+    }
+
+
+    #[test]
+    fn removing_a_missing_id_gives_the_collection_back() {
+        let items = Collection::new().add_many(&[item(1, "one"), item(2, "two")]);
+
+        let missing = items.clone().remove(&3);
+        assert_eq!(missing, items);
+        assert_eq!(missing.validate(), Ok(()));
+
+        let twice = items.clone().remove(&1).remove(&1);
+        assert_eq!(twice, Collection::new().add(&item(2, "two")));
+        assert_eq!(twice.validate(), Ok(()));
+
+        let empty = Collection::<Item>::new().remove(&1);
+        assert!(empty.is_empty());
+        assert_eq!(empty.validate(), Ok(()));
+    }
+
+    #[test]
+    fn dispatching_a_removal_of_a_missing_id_changes_nothing() {
+        let mut store = Store::new(Collection::new());
+        store.register_reducer(Box::new(crate::entity_reducer));
+        store.dispatch(EntityAction::AddEntity(item(1, "one"))).unwrap();
+        let before = store.get_state();
+
+        store.dispatch(EntityAction::RemoveEntity(2)).unwrap();
+
+        assert_eq!(store.get_state(), before);
+        assert_eq!(store.get_state().validate(), Ok(()));
+    }
+
+
+    #[test]
+    fn sorted_entities_move_when_their_key_changes() {
+        let by_name = Collection::new_sorted(|a: &Item, b: &Item| a.name.cmp(b.name));
+        let items = by_name.add_many(&[item(1, "banana"), item(2, "cherry"), item(3, "apple")]);
+        assert_eq!(ids(&items), vec![3, 1, 2]);
+
+        let items = items.update(&item(3, "damson"));
+        assert_eq!(ids(&items), vec![1, 2, 3]);
+
+        let items = items.upsert(&item(2, "apricot")).map_one(&1, |item| item.name = "elderberry");
+        assert_eq!(ids(&items), vec![2, 3, 1]);
+        assert_eq!(items.validate(), Ok(()));
+    }
+
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_round_trips_in_order_and_refuses_a_repeated_id() {
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        struct Named {
+            id: i32,
+            name: String,
+        }
+
+        impl Identifiable for Named {
+            type Id = i32;
+
+            fn get_id(&self) -> i32 {
+                self.id
+            }
+        }
+
+        let named = |id, name: &str| Named { id, name: name.to_string() };
+        let items = Collection::new().add_many(&[named(2, "two"), named(1, "one")]);
+        let json = serde_json::to_string(&items).unwrap();
+        assert_eq!(json, r#"[{"id":2,"name":"two"},{"id":1,"name":"one"}]"#);
+        assert_eq!(serde_json::from_str::<Collection<Named>>(&json).unwrap(), items);
+
+        let repeated = serde_json::from_str::<Collection<Named>>(r#"[{"id":1,"name":"one"},{"id":1,"name":"again"}]"#);
+        assert_eq!(repeated.unwrap_err().to_string(), "duplicate id 1 in collection");
+    }
+
+
+    #[test]
+    fn taking_hands_back_the_entity_removed() {
+        let items = Collection::new().add_many(&[item(1, "one"), item(2, "two"), item(3, "three")]);
+
+        let (taken, removed) = items.clone().take(&2);
+        assert_eq!(removed, Some(item(2, "two")));
+        assert_eq!(ids(&taken), vec![1, 3]);
+        assert_eq!(taken.validate(), Ok(()));
+
+        let (missing, removed) = items.clone().take(&4);
+        assert_eq!(removed, None);
+        assert_eq!(missing, items);
+    }
+
+
+    #[test]
+    fn collecting_and_extending_keep_the_first_of_an_id() {
+        let collected: Collection<Item> = vec![item(1, "one"), item(2, "two"), item(1, "again")].into_iter().collect();
+        assert_eq!(collected.iter().map(|item| item.name).collect::<Vec<_>>(), vec!["one", "two"]);
+        assert_eq!((collected.ids().len(), collected.len()), (2, 2));
+        assert_eq!(collected.validate(), Ok(()));
+
+        let mut extended = collected;
+        extended.extend(vec![item(2, "again"), item(3, "three"), item(3, "and again")]);
+        assert_eq!(extended.iter().map(|item| item.name).collect::<Vec<_>>(), vec!["one", "two", "three"]);
+        assert_eq!((extended.ids().len(), extended.len()), (3, 3));
+        assert_eq!(extended.validate(), Ok(()));
+    }
+
+
+    #[test]
+    fn dispatching_an_update_clones_only_that_entity() {
+        let clones = Arc::new(Mutex::new(0));
+        let items: Collection<Counted> = (0..100).map(|id| Counted { id, done: false, clones: clones.clone() }).collect();
+        *clones.lock().unwrap() = 0;
+
+        // marks the id dispatched done
+        let mut store = Store::new(items);
+        store.register_reducer(Box::new(|items: &Collection<Counted>, id: &i32| items.clone().map_one(id, |item| item.done = true)));
+        store.dispatch(42).unwrap();
+
+        assert_eq!(*clones.lock().unwrap(), 1);
+        assert!(store.get_state().get(&42).unwrap().done);
+        assert_eq!(store.get_state().count_where(|item| item.done), 1);
+    }
+}
@@ -0,0 +1,47 @@
+use std::time::SystemTime;
+
+// Where the store gets dispatch times from, so tests can fix them
+pub trait Clock {
+    fn now(&self) -> SystemTime;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+// What a caller can attach to an action, with Store::dispatch_with_meta
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DispatchMeta {
+    // e.g. to match an optimistic update with the server's reply
+    pub correlation_id: Option<String>,
+}
+
+// An action as dispatched. Sequence numbers go up by one with every action the store dispatches,
+// follow-ups included, so order actions even when timestamps are equal. Follow-ups share the
+// transaction of the action they follow on from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DispatchedAction<Action> {
+    pub action: Action,
+    pub sequence: u64,
+    pub transaction: u64,
+    pub timestamp: SystemTime,
+    pub correlation_id: Option<String>,
+}
+
+impl<Action> DispatchedAction<Action> {
+    // the same dispatch with a different action, e.g. for a middleware to pass on
+    pub fn map<B>(self, f: impl FnOnce(Action) -> B) -> DispatchedAction<B> {
+        DispatchedAction {
+            action: f(self.action),
+            sequence: self.sequence,
+            transaction: self.transaction,
+            timestamp: self.timestamp,
+            correlation_id: self.correlation_id,
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use std::fmt::Debug;
+use std::hash::Hash;
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+
+use crate::collection::{Collection, Update};
+
+pub trait Identifiable {
+    type Id: Eq + Hash + Clone + Debug;
+
+    fn get_id(&self) -> Self::Id;
+
+    // For re-keying, e.g. swapping an optimistic id for the one the server assigned. Entities that
+    // can't be re-keyed leave it out, and change_id leaves them as they were.
+    fn set_id(&mut self, _id: Self::Id) {}
+}
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+pub enum EntityAction<T: Identifiable> {
+    AddEntity(T),
+    RemoveEntity(T::Id),
+    ReplaceEntity(T),
+    UpsertEntity(T),
+    AddMany(Vec<T>),
+    RemoveMany(Vec<T::Id>),
+    UpsertMany(Vec<T>),
+    // changes are closures, so there's nothing to deserialize
+    #[cfg_attr(feature = "serde", serde(skip))]
+    UpdateMany(Vec<Update<T>>),
+    // the collection's duplicate policy deciding which of a repeated id is kept
+    ReplaceAll(Vec<T>),
+    // as Collection::set_all: a repeated id keeps its first position and its last value, whatever the
+    // policy, e.g. for a refresh where later entries are newer
+    SetAll(Vec<T>),
+    RemoveAll,
+    MoveEntity(T::Id, usize),
+    ChangeId { old: T::Id, new: T::Id },
+}
+
+pub fn entity_reducer<Entity: Identifiable + Clone>(entity_state: &Collection<Entity>, action: &EntityAction<Entity>) -> Collection<Entity> {
+
+    let entity_state = entity_state.clone();
+
+    match action {
+        EntityAction::AddEntity(entity) => entity_state.add(entity),
+        EntityAction::ReplaceEntity(entity) => entity_state.update(entity),
+        EntityAction::UpsertEntity(entity) => entity_state.upsert(entity),
+        EntityAction::AddMany(entities) => entity_state.add_many(entities),
+        EntityAction::RemoveMany(ids) => entity_state.remove_many(ids),
+        EntityAction::UpsertMany(entities) => entity_state.upsert_many(entities),
+        EntityAction::UpdateMany(updates) => entity_state.update_many(updates).0,
+        EntityAction::RemoveEntity(id) => entity_state.remove(id),
+        EntityAction::ReplaceAll(entities) => entity_state.replace_all(entities),
+        EntityAction::SetAll(entities) => entity_state.set_all(entities.clone()),
+        EntityAction::RemoveAll => entity_state.clear(),
+        EntityAction::MoveEntity(id, index) => entity_state.move_to(id, *index),
+        EntityAction::ChangeId { old, new } => entity_state.change_id(old, new.clone()),
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use crate::{CollectionDiff, Store};
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Item {
+        id: i32,
+    }
+
+    impl Identifiable for Item {
+        type Id = i32;
+
+        fn get_id(&self) -> i32 {
+            self.id
+        }
+    }
+
+    fn item(id: i32) -> Item {
+        Item { id }
+    }
+
+    #[test]
+    fn replacing_all_drops_what_isnt_listed() {
+        let items = Collection::new().add_many(&[item(1), item(2), item(3)]);
+
+        let items = entity_reducer(&items, &EntityAction::ReplaceAll(vec![item(3), item(4)]));
+
+        assert_eq!(items.iter().map(|item| item.id).collect::<Vec<_>>(), vec![3, 4]);
+        assert!(!items.contains(&1));
+
+        let items = entity_reducer(&items, &EntityAction::RemoveAll);
+        assert!(items.is_empty());
+    }
+
+    #[test]
+    fn entities_without_set_id_keep_theirs() {
+        let items = Collection::new().add_many(&[item(1), item(2)]);
+
+        let items = entity_reducer(&items, &EntityAction::ChangeId { old: 1, new: 10 });
+
+        assert_eq!(items.iter().map(|item| item.id).collect::<Vec<_>>(), vec![1, 2]);
+        assert!(!items.contains(&10));
+        assert_eq!(items.validate(), Ok(()));
+    }
+
+    #[test]
+    fn replacing_all_is_one_change() {
+        let mut store = Store::new(Collection::new().add_many(&[item(1), item(2), item(3)]));
+        store.register_reducer(Box::new(entity_reducer));
+        let notified = Arc::new(Mutex::new(0));
+        let observed = notified.clone();
+        store.subscribe(move |_| *observed.lock().unwrap() += 1);
+        let before = store.get_state();
+
+        store.dispatch(EntityAction::ReplaceAll(vec![item(4), item(3)])).unwrap();
+
+        assert_eq!(*notified.lock().unwrap(), 1);
+        assert_eq!(store.get_state().iter().cloned().collect::<Vec<_>>(), vec![item(4), item(3)]);
+        assert_eq!(store.changes_since(&before), CollectionDiff { added: vec![4], removed: vec![1, 2], changed: vec![], moved: vec![] });
+    }
+
+    #[test]
+    fn changes_since_a_snapshot_are_reported() {
+        let mut store = Store::new(Collection::new().add_many(&[item(1), item(2), item(3)]));
+        store.register_reducer(Box::new(entity_reducer));
+        let snapshot = store.get_state();
+
+        store.dispatch(EntityAction::AddEntity(item(4))).unwrap();
+        store.dispatch(EntityAction::MoveEntity(1, 2)).unwrap();
+
+        assert_eq!(store.changes_since(&snapshot), CollectionDiff { added: vec![4], removed: vec![], changed: vec![], moved: vec![1] });
+        assert!(store.changes_since(&store.get_state()).is_empty());
+    }
+
+    #[test]
+    fn action_observers_hear_which_entity_was_removed() {
+        let mut store = Store::new(Collection::new().add_many(&[item(1), item(2)]));
+        store.register_reducer(Box::new(entity_reducer));
+        let removed = Arc::new(Mutex::new(vec![]));
+        let observed = removed.clone();
+        store.observe_with_action(|items| items.len(), move |action, len| {
+            if let EntityAction::RemoveEntity(id) = action {
+                observed.lock().unwrap().push((*id, *len));
+            }
+        });
+
+        store.dispatch(EntityAction::AddEntity(item(3))).unwrap();
+        store.dispatch(EntityAction::RemoveEntity(2)).unwrap();
+
+        assert_eq!(*removed.lock().unwrap(), vec![(2, 2)]);
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Tag {
+        name: String,
+        uses: u32,
+    }
+
+    impl Identifiable for Tag {
+        type Id = String;
+
+        fn get_id(&self) -> String {
+            self.name.clone()
+        }
+    }
+
+    fn tag(name: &str, uses: u32) -> Tag {
+        Tag { name: String::from(name), uses }
+    }
+
+    #[test]
+    fn ids_can_be_strings() {
+        let tags = Collection::new().add_many(&[tag("rust", 1), tag("redux", 2)]);
+
+        let tags = entity_reducer(&tags, &EntityAction::ReplaceEntity(tag("rust", 5)));
+        let tags = entity_reducer(&tags, &EntityAction::RemoveEntity(String::from("redux")));
+
+        assert_eq!(tags.get(&String::from("rust")), Some(&tag("rust", 5)));
+        assert!(!tags.contains(&String::from("redux")));
+        assert_eq!(tags.ids().iter().cloned().collect::<Vec<_>>(), vec![String::from("rust")]);
+    }
+
+    #[test]
+    fn upserting_adds_or_replaces_in_place() {
+        let tags = Collection::new().add_many(&[tag("a", 1), tag("b", 1), tag("c", 1)]);
+
+        let tags = entity_reducer(&tags, &EntityAction::UpsertEntity(tag("b", 2)));
+        let tags = entity_reducer(&tags, &EntityAction::UpsertEntity(tag("d", 1)));
+        let tags = entity_reducer(&tags, &EntityAction::UpsertEntity(tag("d", 3)));
+
+        assert_eq!(tags.iter().cloned().collect::<Vec<_>>(), vec![tag("a", 1), tag("b", 2), tag("c", 1), tag("d", 3)]);
+        assert_eq!(tags.validate(), Ok(()));
+    }
+
+    #[test]
+    fn replacing_a_tag_never_added_leaves_ids_and_entities_in_step() {
+        let tags = Collection::new().add_many(&[tag("a", 1)]);
+
+        let replaced = entity_reducer(&tags, &EntityAction::ReplaceEntity(tag("b", 2)));
+
+        // validate finds any entity without its id, as well as any id without its entity
+        assert_eq!(replaced, tags);
+        assert_eq!(replaced.validate(), Ok(()));
+    }
+
+    #[test]
+    fn setting_all_keeps_the_last_of_a_repeated_id_where_replacing_keeps_the_first() {
+        let tags = Collection::new().add_many(&[tag("a", 1), tag("b", 1), tag("c", 1)]);
+        let incoming = vec![tag("b", 2), tag("d", 1), tag("b", 3)];
+
+        let set = entity_reducer(&tags, &EntityAction::SetAll(incoming.clone()));
+        assert_eq!(set.iter().cloned().collect::<Vec<_>>(), vec![tag("b", 3), tag("d", 1)]);
+        assert_eq!(set.validate(), Ok(()));
+
+        let replaced = entity_reducer(&tags, &EntityAction::ReplaceAll(incoming));
+        assert_eq!(replaced.iter().cloned().collect::<Vec<_>>(), vec![tag("b", 2), tag("d", 1)]);
+    }
+
+    #[test]
+    fn batches_apply_in_one_dispatch() {
+        let mut store = Store::new(Collection::new());
+        store.register_reducer(Box::new(entity_reducer));
+
+        store.dispatch(EntityAction::AddMany((0..1000).map(item).collect())).unwrap();
+        assert_eq!(store.get_state().len(), 1000);
+        assert_eq!(store.version(), 1);
+
+        // 1 is listed twice, and 5000 was never there
+        store.dispatch(EntityAction::RemoveMany(vec![1, 1, 3, 5000])).unwrap();
+        store.dispatch(EntityAction::UpsertMany(vec![item(2), item(1000)])).unwrap();
+
+        let items = store.get_state();
+        assert_eq!(items.iter().take(3).map(|item| item.id).collect::<Vec<_>>(), vec![0, 2, 4]);
+        assert_eq!(items.len(), 999);
+        assert_eq!(items.ids().last(), Some(&1000));
+        assert_eq!(items.validate(), Ok(()));
+    }
+}
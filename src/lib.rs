@@ -0,0 +1,126 @@
+pub mod actor;
+pub mod builder;
+pub mod collection;
+pub mod dispatched;
+pub mod entity;
+pub mod logger;
+#[cfg(feature = "serde")]
+pub mod persist;
+pub mod scoped;
+pub mod selector;
+pub mod shared;
+pub mod store;
+pub mod test_util;
+pub mod undo;
+
+pub use actor::StoreHandle;
+pub use builder::StoreBuilder;
+pub use collection::{Collection, CollectionDiff, DuplicatePolicy, Update};
+pub use dispatched::{Clock, DispatchedAction, DispatchMeta, SystemClock};
+pub use entity::{entity_reducer, EntityAction, Identifiable};
+pub use logger::LoggerMiddleware;
+#[cfg(feature = "serde")]
+pub use persist::{PersistConfig, PersistMiddleware};
+pub use scoped::ScopedStore;
+pub use selector::{create_selector, Memoized};
+pub use shared::SharedStore;
+pub use store::{combine_reducers, slice_reducer, try_slice_reducer, ActionObserver, CheckedAction, DispatchCtx, DispatchError, Dispatcher, Effect, FallibleReducer, HistoryEntry, IdempotencyKey, IsNoop, Middleware, Next, Observer, ObserverBand, ObserverErrorHandler, Reducer, ReducerHandle, Selector, SliceReducer, Store, SubscriptionHandle};
+pub use undo::{undoable, Undoable, UndoableAction, UndoAction};
+
+// Heap memory owned by a value, not counting the value itself
+#[cfg(feature = "heap-size")]
+pub trait HeapSize {
+    fn heap_size(&self) -> usize;
+}
+
+// Id is the id type of the entities involved, i32 unless given
+#[derive(Debug)]
+pub enum Error<Id = i32> {
+    InvalidOrder(Vec<Id>),
+    NotFound(Id),
+    DuplicateId(Id),
+    // a reducer refused a dispatched action
+    Dispatch(Box<DispatchError>),
+    // what a StoreBuilder was built without
+    NoInitialState,
+    NoReducers,
+    #[cfg(feature = "serde")]
+    Json(serde_json::Error),
+    #[cfg(feature = "serde")]
+    Io(std::io::Error),
+}
+
+impl From<DispatchError> for Error {
+    fn from(failure: DispatchError) -> Self {
+        Error::Dispatch(Box::new(failure))
+    }
+}
+
+// What changed going from a previous value to this one
+pub trait Diffable {
+    type Diff;
+
+    fn diff(&self, prev: &Self) -> Self::Diff;
+}
+
+// Which top-level fields of a state differ, so an observer on the whole state can branch cheaply
+pub trait FieldChanges {
+    fn changed_fields(&self, prev: &Self) -> Vec<&'static str>;
+}
+
+// Implements FieldChanges over the listed fields, e.g. field_changes!(RootState { todos });
+// Fields are compared with ==, which for a Collection is a pointer check when nothing changed.
+#[macro_export]
+macro_rules! field_changes {
+    ($state:ty { $($field:ident),+ $(,)? }) => {
+        impl $crate::FieldChanges for $state {
+            fn changed_fields(&self, prev: &Self) -> Vec<&'static str> {
+                let mut changed = vec![];
+                $(
+                    if self.$field != prev.$field {
+                        changed.push(stringify!($field));
+                    }
+                )+
+                changed
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Item {
+        id: i32,
+    }
+
+    impl Identifiable for Item {
+        type Id = i32;
+
+        fn get_id(&self) -> i32 {
+            self.id
+        }
+    }
+
+    #[derive(Clone, PartialEq)]
+    struct State {
+        items: Collection<Item>,
+        counter: i32,
+    }
+
+    field_changes!(State { items, counter });
+
+    #[test]
+    fn only_the_fields_changed_are_reported() {
+        let before = State { items: Collection::new().add(&Item { id: 1 }), counter: 0 };
+
+        let added = State { items: before.items.clone().add(&Item { id: 2 }), ..before.clone() };
+        assert_eq!(added.changed_fields(&before), vec!["items"]);
+
+        let counted = State { counter: 1, ..before.clone() };
+        assert_eq!(counted.changed_fields(&before), vec!["counter"]);
+        assert!(before.clone().changed_fields(&before).is_empty());
+    }
+}
@@ -0,0 +1,96 @@
+use std::fmt::Debug;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::dispatched::DispatchedAction;
+use crate::store::{Middleware, Next};
+
+// Writes a line per action: its sequence number, the action, how long the rest of the dispatch
+// took and whether the state changed, e.g.
+// #3 MarkDone(1, true) took 15.2µs, state changed
+// Apply it first, so the time covers the other middlewares too.
+pub struct LoggerMiddleware<State, W> {
+    output: Arc<Mutex<W>>,
+    changed: Option<fn(&State, &State) -> bool>,
+}
+
+impl<State: PartialEq, W: Write> LoggerMiddleware<State, W> {
+    pub fn new(output: W) -> Self {
+        LoggerMiddleware { changed: Some(|before, after| before != after), ..LoggerMiddleware::without_change_check(output) }
+    }
+}
+
+impl<State, W: Write> LoggerMiddleware<State, W> {
+    // for states that can't be compared, which are logged as unchecked
+    pub fn without_change_check(output: W) -> Self {
+        LoggerMiddleware { output: Arc::new(Mutex::new(output)), changed: None }
+    }
+
+    // shared with the logger, e.g. to read back what was logged to a Vec<u8>
+    pub fn output(&self) -> Arc<Mutex<W>> {
+        self.output.clone()
+    }
+}
+
+impl<State, Action, E, W> Middleware<State, Action, E> for LoggerMiddleware<State, W>
+    where State: Clone, Action: Clone + Debug, W: Write {
+
+    fn handle(&self, action: DispatchedAction<Action>, next: &mut Next<'_, State, Action, E>) {
+
+        let line = format!("#{} {:?}", action.sequence, action.action);
+        let before = self.changed.map(|_| next.get_state());
+
+        let started = Instant::now();
+        next.pass(action);
+        let elapsed = started.elapsed();
+
+        let change = match (self.changed, before) {
+            // the same snapshot can't have changed
+            (Some(changed), Some(before)) if !Arc::ptr_eq(&before, &next.get_state()) && changed(&before, next.state()) => "state changed",
+            (Some(_), _) => "state unchanged",
+            (None, _) => "state change unchecked",
+        };
+
+        let mut output = self.output.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        // a logger failing to write shouldn't fail the dispatch
+        let _ = writeln!(output, "{} took {:?}, {}", line, elapsed, change);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Store;
+
+    fn logged(logger: LoggerMiddleware<i32, Vec<u8>>, actions: &[i32]) -> Vec<String> {
+        let output = logger.output();
+        let mut store: Store<i32, i32> = Store::new(0);
+        store.register_reducer(Box::new(|count, n| count + n));
+        store.apply_middleware(logger);
+
+        actions.iter().for_each(|&n| store.dispatch(n).unwrap());
+
+        let output = String::from_utf8(output.lock().unwrap().clone()).unwrap();
+        output.lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn logs_a_line_per_dispatch() {
+        let lines = logged(LoggerMiddleware::new(vec![]), &[1, 0]);
+
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("#1 1 took "), "{}", lines[0]);
+        assert!(lines[0].ends_with(", state changed"), "{}", lines[0]);
+        assert!(lines[1].starts_with("#2 0 took "), "{}", lines[1]);
+        assert!(lines[1].ends_with(", state unchanged"), "{}", lines[1]);
+    }
+
+    #[test]
+    fn states_that_cant_be_compared_are_logged_unchecked() {
+        let lines = logged(LoggerMiddleware::without_change_check(vec![]), &[1]);
+
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].ends_with(", state change unchecked"), "{}", lines[0]);
+    }
+}
@@ -0,0 +1,173 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc::{self, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::dispatched::DispatchedAction;
+use crate::store::{Middleware, Next};
+
+pub type PersistErrorHandler = dyn Fn(&io::Error) + Send + Sync;
+
+#[derive(Clone)]
+pub struct PersistConfig {
+    // how long dispatches have to stop for before the state's written
+    pub debounce: Duration,
+    // told of failed writes, which are otherwise printed to stderr
+    pub on_error: Option<Arc<PersistErrorHandler>>,
+}
+
+impl Default for PersistConfig {
+    fn default() -> Self {
+        PersistConfig { debounce: Duration::from_millis(500), on_error: None }
+    }
+}
+
+// Saves the state to a JSON file as it changes, for Store::rehydrate_from to load at startup.
+// Writing happens on a thread of its own, once dispatches have stopped for the debounce, so a burst
+// of them is written once. Each write goes to a temporary file which then replaces the last, so a
+// crash mid-write can't leave a half written file. Dropping the middleware (with its store) writes
+// anything still waiting.
+pub struct PersistMiddleware<State> {
+    sender: Option<Sender<State>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl<State: Serialize + Send + 'static> PersistMiddleware<State> {
+    pub fn new(path: impl AsRef<Path>, config: PersistConfig) -> Self {
+
+        let path = path.as_ref().to_path_buf();
+        let (sender, receiver) = mpsc::channel();
+
+        let writer = thread::spawn(move || {
+            while let Ok(mut state) = receiver.recv() {
+
+                // the latest state once they stop coming, or the sender's gone
+                while let Ok(newer) = receiver.recv_timeout(config.debounce) {
+                    state = newer;
+                }
+
+                if let Err(error) = write_atomically(&path, &state) {
+                    match &config.on_error {
+                        Some(on_error) => on_error(&error),
+                        None => eprintln!("couldn't persist the state to {}: {}", path.display(), error),
+                    }
+                }
+            }
+        });
+
+        PersistMiddleware { sender: Some(sender), writer: Some(writer) }
+    }
+}
+
+fn write_atomically<State: Serialize>(path: &Path, state: &State) -> io::Result<()> {
+
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let temporary = PathBuf::from(temporary);
+
+    fs::write(&temporary, serde_json::to_vec(state)?)?;
+    fs::rename(&temporary, path)
+}
+
+impl<State, Action, E> Middleware<State, Action, E> for PersistMiddleware<State>
+    where State: Clone, Action: Clone {
+
+    fn handle(&self, action: DispatchedAction<Action>, next: &mut Next<'_, State, Action, E>) {
+        next.pass(action);
+
+        if let Some(sender) = &self.sender {
+            // the writer only goes once this does
+            let _ = sender.send(next.state().clone());
+        }
+    }
+}
+
+impl<State> Drop for PersistMiddleware<State> {
+    fn drop(&mut self) {
+        self.sender.take();
+
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    use crate::Store;
+
+    fn counting_store() -> Store<i32, i32> {
+        let mut store = Store::new(0);
+        store.register_reducer(Box::new(|count, n| count + n));
+        store
+    }
+
+    // a directory of the test's own, emptied of any earlier run's files
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rs-redux-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn a_new_store_rehydrates_what_was_persisted() {
+        let path = temp_dir("rehydrates").join("state.json");
+        let config = PersistConfig { debounce: Duration::from_millis(20), ..Default::default() };
+
+        let mut store = counting_store();
+        store.apply_middleware(PersistMiddleware::new(&path, config));
+        (1..=4).for_each(|n| store.dispatch(n).unwrap());
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "10");
+
+        let mut rehydrated = counting_store();
+        rehydrated.rehydrate_from(&path).unwrap();
+        assert_eq!(*rehydrated.get_state(), 10);
+
+        // dropping the store writes what was still waiting out the debounce
+        store.dispatch(5).unwrap();
+        drop(store);
+        rehydrated.rehydrate_from(&path).unwrap();
+        assert_eq!(*rehydrated.get_state(), 15);
+    }
+
+    #[test]
+    fn a_missing_or_corrupt_file_keeps_the_state() {
+        let dir = temp_dir("corrupt");
+        let mut store = counting_store();
+        store.dispatch(1).unwrap();
+
+        store.rehydrate_from(dir.join("missing.json")).unwrap();
+        assert_eq!(*store.get_state(), 1);
+
+        fs::write(dir.join("state.json"), "{ not json").unwrap();
+        assert!(matches!(store.rehydrate_from(dir.join("state.json")), Err(crate::Error::Json(_))));
+        assert_eq!(*store.get_state(), 1);
+    }
+
+    #[test]
+    fn failed_writes_are_reported_rather_than_panicking() {
+        let errors = Arc::new(Mutex::new(0));
+        let counted = errors.clone();
+        let config = PersistConfig {
+            debounce: Duration::from_millis(1),
+            on_error: Some(Arc::new(move |_: &io::Error| *counted.lock().unwrap() += 1)),
+        };
+
+        let mut store = counting_store();
+        store.apply_middleware(PersistMiddleware::new(temp_dir("unwritable").join("missing").join("state.json"), config));
+        store.dispatch(1).unwrap();
+        drop(store);
+
+        assert_eq!(*errors.lock().unwrap(), 1);
+    }
+}
@@ -0,0 +1,127 @@
+use std::rc::Rc;
+
+use crate::Error;
+use crate::store::{DispatchError, Store, SubscriptionHandle};
+
+// The part of the state a scoped store looks at
+type Lens<State, Slice> = dyn Fn(&State) -> Slice;
+
+// A store seen through a lens, e.g. for a component that only knows about the todos:
+// let mut todos = store.scope(|state: &RootState| state.todos.clone());
+// Dispatching still goes to the whole store, and its reducers. Observers are the store's own,
+// only hearing of changes to the slice.
+pub struct ScopedStore<'a, State, Slice, Action, E = Error> {
+    store: &'a mut Store<State, Action, E>,
+    lens: Rc<Lens<State, Slice>>,
+}
+
+impl<State, Action, E> Store<State, Action, E> where State: Clone + 'static, Action: Clone + 'static {
+    pub fn scope<Slice>(&mut self, lens: impl Fn(&State) -> Slice + 'static) -> ScopedStore<'_, State, Slice, Action, E> {
+        ScopedStore { store: self, lens: Rc::new(lens) }
+    }
+}
+
+impl<'a, State, Slice, Action, E> ScopedStore<'a, State, Slice, Action, E>
+    where State: Clone + 'static, Slice: PartialEq + 'static, Action: Clone + 'static {
+
+    pub fn get_state(&self) -> Slice {
+        (self.lens)(&self.store.get_state())
+    }
+
+    pub fn dispatch(&mut self, action: Action) -> Result<(), DispatchError<E>> {
+        self.store.dispatch(action)
+    }
+
+    // selector picks from the slice, and observer only hears of it when the slice has changed,
+    // however much else of the state did. Unsubscribe through the store.
+    pub fn observe<S: 'static>(&mut self, selector: impl Fn(&Slice) -> S + 'static, observer: impl Fn(&S) + 'static) -> SubscriptionHandle {
+        let lens = self.lens.clone();
+        self.store.observe_distinct(move |state| lens(state), move |slice| observer(&selector(slice)))
+    }
+
+    // a scope within this one, e.g. a single todo of the todos
+    pub fn scope<Inner>(&mut self, lens: impl Fn(&Slice) -> Inner + 'static) -> ScopedStore<'_, State, Inner, Action, E> {
+        let outer = self.lens.clone();
+        ScopedStore { store: &mut *self.store, lens: Rc::new(move |state| lens(&outer(state))) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+    use crate::{entity_reducer, Collection, EntityAction, Identifiable};
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Todo {
+        id: i32,
+        done: bool,
+    }
+
+    impl Identifiable for Todo {
+        type Id = i32;
+
+        fn get_id(&self) -> i32 {
+            self.id
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct RootState {
+        todos: Collection<Todo>,
+        counter: i32,
+    }
+
+    #[derive(Debug, Clone)]
+    enum Action {
+        Todos(EntityAction<Todo>),
+        Count,
+    }
+
+    fn root_store() -> Store<RootState, Action> {
+        let mut store = Store::new(RootState { todos: Collection::new(), counter: 0 });
+        store.register_reducer(Box::new(|state: &RootState, action| match action {
+            Action::Todos(action) => RootState { todos: entity_reducer(&state.todos, action), ..state.clone() },
+            Action::Count => RootState { counter: state.counter + 1, ..state.clone() },
+        }));
+        store
+    }
+
+    #[test]
+    fn scoped_observers_only_hear_of_their_slice_changing() {
+        let mut store = root_store();
+        let seen = Rc::new(RefCell::new(vec![]));
+        let observed = seen.clone();
+
+        let mut todos = store.scope(|state: &RootState| state.todos.clone());
+        todos.observe(|todos| todos.len(), move |len| observed.borrow_mut().push(*len));
+
+        // the first change of all is heard, as a first notification always is
+        todos.dispatch(Action::Todos(EntityAction::AddEntity(Todo { id: 1, done: false }))).unwrap();
+        store.dispatch(Action::Count).unwrap();
+        store.dispatch(Action::Todos(EntityAction::AddEntity(Todo { id: 2, done: false }))).unwrap();
+        store.dispatch(Action::Count).unwrap();
+
+        assert_eq!(*seen.borrow(), vec![1, 2]);
+        assert_eq!(store.get_state().counter, 2);
+    }
+
+    #[test]
+    fn scopes_nest() {
+        let mut store = root_store();
+        store.dispatch(Action::Todos(EntityAction::AddEntity(Todo { id: 1, done: false }))).unwrap();
+        let seen = Rc::new(RefCell::new(vec![]));
+        let observed = seen.clone();
+
+        let mut todos = store.scope(|state: &RootState| state.todos.clone());
+        let mut first = todos.scope(|todos| todos.get(&1).cloned());
+        first.observe(|todo| todo.as_ref().map(|todo| todo.done), move |done| observed.borrow_mut().push(*done));
+
+        first.dispatch(Action::Todos(EntityAction::AddEntity(Todo { id: 2, done: false }))).unwrap();
+        first.dispatch(Action::Todos(EntityAction::UpsertEntity(Todo { id: 1, done: true }))).unwrap();
+        assert_eq!(first.get_state(), Some(Todo { id: 1, done: true }));
+
+        assert_eq!(*seen.borrow(), vec![Some(false), Some(true)]);
+    }
+}
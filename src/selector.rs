@@ -0,0 +1,110 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+struct Memo<State, Input, Output> {
+    input: Box<dyn Fn(&State) -> Input>,
+    combine: Box<dyn Fn(&Input) -> Output>,
+    last: RefCell<Option<(Input, Output)>>,
+    recomputed: Cell<usize>,
+}
+
+// A selector remembering its last input and output, only combining again once the input changes.
+// Clones share the memo.
+pub struct Memoized<State, Input, Output> {
+    memo: Rc<Memo<State, Input, Output>>,
+}
+
+impl<State, Input, Output> Clone for Memoized<State, Input, Output> {
+    fn clone(&self) -> Self {
+        Memoized { memo: self.memo.clone() }
+    }
+}
+
+// Selects with input, then combine, unless input selects what it did last time. Several inputs can
+// be selected as a tuple, e.g.
+// create_selector(|state: &RootState| (state.todos.clone(), state.filter), |(todos, filter)| ...)
+// and memoized selectors can be inputs themselves, through Memoized::selector.
+pub fn create_selector<State, Input, Output>(input: impl Fn(&State) -> Input + 'static, combine: impl Fn(&Input) -> Output + 'static) -> Memoized<State, Input, Output>
+    where Input: PartialEq, Output: Clone {
+
+    Memoized {
+        memo: Rc::new(Memo { input: Box::new(input), combine: Box::new(combine), last: RefCell::new(None), recomputed: Cell::new(0) }),
+    }
+}
+
+impl<State, Input: PartialEq, Output: Clone> Memoized<State, Input, Output> {
+    pub fn select(&self, state: &State) -> Output {
+
+        let memo = &self.memo;
+        let input = (memo.input)(state);
+
+        if let Some((last_input, output)) = &*memo.last.borrow() {
+            if *last_input == input {
+                return output.clone();
+            }
+        }
+
+        let output = (memo.combine)(&input);
+        memo.recomputed.set(memo.recomputed.get() + 1);
+        memo.last.replace(Some((input, output.clone())));
+
+        output
+    }
+
+    // how many times combine has run
+    pub fn recompute_count(&self) -> usize {
+        self.memo.recomputed.get()
+    }
+
+    // as a plain selector, e.g. to observe with, sharing this memo
+    pub fn selector(&self) -> impl Fn(&State) -> Output {
+        let memoized = self.clone();
+        move |state| memoized.select(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{entity_reducer, Collection, EntityAction, Identifiable, Store};
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Todo {
+        id: i32,
+        done: bool,
+    }
+
+    impl Identifiable for Todo {
+        type Id = i32;
+
+        fn get_id(&self) -> i32 {
+            self.id
+        }
+    }
+
+    #[test]
+    fn a_chain_only_recombines_what_changed() {
+        let mut store = Store::new(Collection::new());
+        store.register_reducer(Box::new(entity_reducer));
+
+        let select_todos = create_selector(|todos: &Collection<Todo>| todos.clone(), |todos| todos.clone());
+        let select_todo_2 = create_selector(select_todos.selector(), |todos| todos.get(&2).cloned());
+        let select_done = create_selector(select_todo_2.selector(), |todo| todo.as_ref().map(|todo| todo.done));
+
+        let seen = Rc::new(RefCell::new(vec![]));
+        let observed = seen.clone();
+        store.observe(select_done.selector(), move |done| observed.borrow_mut().push(*done));
+
+        store.dispatch(EntityAction::AddMany(vec![Todo { id: 1, done: false }, Todo { id: 2, done: false }])).unwrap();
+        store.dispatch(EntityAction::UpsertEntity(Todo { id: 1, done: true })).unwrap();
+        store.dispatch(EntityAction::AddEntity(Todo { id: 3, done: false })).unwrap();
+
+        // every change reaches the todos, but todo 2 is the same each time after the first
+        assert_eq!((select_todos.recompute_count(), select_todo_2.recompute_count(), select_done.recompute_count()), (3, 3, 1));
+        assert_eq!(*seen.borrow(), vec![Some(false); 3]);
+
+        store.dispatch(EntityAction::UpsertEntity(Todo { id: 2, done: true })).unwrap();
+        assert_eq!(select_done.recompute_count(), 2);
+        assert_eq!(seen.borrow().last(), Some(&Some(true)));
+    }
+}
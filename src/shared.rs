@@ -0,0 +1,302 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::Error;
+use crate::store::{DispatchError, ReducerHandle, Store, SubscriptionHandle};
+
+// An observer and its selector as a single call, as the store's own observers are
+type SharedNotify<State> = dyn Fn(&State) + Send + Sync;
+
+struct SharedObservers<State> {
+    observers: Vec<(SubscriptionHandle, Arc<SharedNotify<State>>)>,
+    next_subscription: usize,
+    // states left by dispatches, in the order applied, for whichever thread is notifying to hand on
+    pending: VecDeque<State>,
+    notifying: bool,
+}
+
+// A Store that's only ever given closures that are Send, so it can be sent even though Store can't
+// say so itself
+struct SendStore<State, Action, E>(Store<State, Action, E>);
+
+// SAFETY: Store isn't Send for what it boxes (reducers, middlewares, effects, observers, the clock)
+// and for its Arc<State> snapshots, which are only Send for a State that's Sync too. A SendStore is
+// only made and reached through SharedStore, which keeps both in hand:
+// - the store is made with nothing boxed but the SystemClock, and the only things SharedStore ever
+//   boxes into it are reducers, which it requires to be Send + Sync. It has no way to apply
+//   middlewares, register effects or observe the store itself, its own observers living outside it.
+// - every Arc<State> (the state, history, the versioned state) stays inside the store, so they all
+//   move between threads together, as a whole. Only deep clones of the state are handed out.
+// - the dispatcher's queues are Arc<Mutex<VecDeque<Action>>>, fine for an Action that's Send, and
+//   dispatch failures hold an E, which must be Send too.
+// Anything added to SharedStore that hands the store something else to box, or a snapshot, breaks this.
+unsafe impl<State: Send, Action: Send, E: Send> Send for SendStore<State, Action, E> {}
+
+// A store that can be shared between threads, by cloning it. Dispatches are applied one at a time.
+// Observers are called once the store is unlocked, so they can use the store themselves, each with
+// a copy of the state a dispatch left, in the order the dispatches were applied. A dispatch made
+// while another thread is notifying returns straight away, that thread notifying of it next; so
+// does one made by an observer, its notification coming once the current one is through.
+pub struct SharedStore<State, Action, E = Error> {
+    store: Arc<Mutex<SendStore<State, Action, E>>>,
+    observers: Arc<Mutex<SharedObservers<State>>>,
+}
+
+impl<State, Action, E> Clone for SharedStore<State, Action, E> {
+    fn clone(&self) -> Self {
+        SharedStore { store: self.store.clone(), observers: self.observers.clone() }
+    }
+}
+
+impl<State, Action> SharedStore<State, Action>
+    where State: Clone + Send + 'static, Action: Clone + Send + 'static {
+
+    pub fn new(state: State) -> Self {
+        SharedStore::with_error(state)
+    }
+}
+
+impl<State, Action, E> SharedStore<State, Action, E>
+    where State: Clone + Send + 'static, Action: Clone + Send + 'static, E: Send + 'static {
+
+    // As Store::with_error
+    pub fn with_error(state: State) -> Self {
+        SharedStore {
+            store: Arc::new(Mutex::new(SendStore(Store::with_error(state)))),
+            observers: Arc::new(Mutex::new(SharedObservers {
+                observers: vec![],
+                next_subscription: 0,
+                pending: VecDeque::new(),
+                notifying: false,
+            })),
+        }
+    }
+
+    // A reducer panicking mid-dispatch poisons the lock. The dispatch it cut short is dropped, so
+    // the store carries on from the last state fully applied.
+    fn store(&self) -> MutexGuard<'_, SendStore<State, Action, E>> {
+        self.store.lock().unwrap_or_else(|poisoned| {
+            let mut store = poisoned.into_inner();
+            store.0.abandon_dispatch();
+            self.store.clear_poison();
+            store
+        })
+    }
+
+    fn observers(&self) -> MutexGuard<'_, SharedObservers<State>> {
+        self.observers.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn register_reducer(&self, reducer: impl Fn(&State, &Action) -> State + Send + Sync + 'static) -> ReducerHandle {
+        self.store().0.register_reducer(Box::new(reducer))
+    }
+
+    pub fn register_fallible_reducer(&self, reducer: impl Fn(&State, &Action) -> Result<State, E> + Send + Sync + 'static) -> ReducerHandle {
+        self.store().0.register_fallible_reducer(Box::new(reducer))
+    }
+
+    pub fn unregister_reducer(&self, handle: ReducerHandle) -> bool {
+        self.store().0.unregister_reducer(handle)
+    }
+
+    // As Store::dispatch, observers going unnotified if nothing changed, e.g. as it failed
+    pub fn dispatch(&self, action: Action) -> Result<(), DispatchError<E>> {
+
+        let (result, notify) = {
+            let mut store = self.store();
+            let changes = store.0.state_changes();
+            let result = store.0.dispatch(action);
+
+            if store.0.state_changes() == changes {
+                return result;
+            }
+
+            // queued while the store's still locked, so in the order dispatches are applied
+            let mut observers = self.observers();
+            observers.pending.push_back(State::clone(&store.0.get_state()));
+            let notify = !observers.notifying;
+            observers.notifying = true;
+            (result, notify)
+        };
+
+        if notify {
+            self.notify_pending();
+        }
+
+        result
+    }
+
+    // Notifies of each state queued, oldest first, until there are none left
+    fn notify_pending(&self) {
+
+        // should an observer panic, the next dispatch notifies of what's left
+        let _unwinding = Notifying(self);
+
+        loop {
+            // copied out so observers can observe or unsubscribe while being notified
+            let (state, observers): (_, Vec<_>) = {
+                let mut observers = self.observers();
+                let state = match observers.pending.pop_front() {
+                    Some(state) => state,
+                    // while still locked, so a dispatch queueing another state after can't miss out
+                    None => {
+                        observers.notifying = false;
+                        break;
+                    },
+                };
+                (state, observers.observers.iter()
+                    .filter(|(subscription, _)| !subscription.is_cancelled())
+                    .map(|(_, notify)| notify.clone())
+                    .collect())
+            };
+
+            observers.iter().for_each(|notify| notify(&state));
+        }
+    }
+
+    // a snapshot, which later dispatches don't change
+    pub fn get_state(&self) -> State {
+        State::clone(&self.store().0.get_state())
+    }
+
+    pub fn select<T>(&self, selector: impl FnOnce(&State) -> T) -> T {
+        selector(&self.get_state())
+    }
+
+    pub fn observe<S>(&self, selector: impl Fn(&State) -> S + Send + Sync + 'static, observer: impl Fn(&S) + Send + Sync + 'static) -> SubscriptionHandle {
+
+        let mut observers = self.observers();
+
+        let subscription = SubscriptionHandle::new(observers.next_subscription);
+        observers.next_subscription += 1;
+
+        observers.observers.push((subscription.clone(), Arc::new(move |state: &State| observer(&selector(state)))));
+
+        subscription
+    }
+
+    // Stops the observer being notified, though a notification already under way may still call it
+    pub fn unsubscribe(&self, subscription: SubscriptionHandle) {
+        subscription.cancel();
+        self.observers().observers.retain(|(observing, _)| !observing.is_cancelled());
+    }
+}
+
+// Lets the next dispatch notify should an observer panic while notifying
+struct Notifying<'a, State, Action, E>(&'a SharedStore<State, Action, E>);
+
+impl<'a, State, Action, E> Drop for Notifying<'a, State, Action, E> {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            self.0.observers.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).notifying = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn counting_store() -> SharedStore<i32, i32> {
+        let store = SharedStore::new(0);
+        store.register_reducer(|count, n| {
+            if *n == 13 {
+                panic!("unlucky");
+            }
+            count + n
+        });
+        store
+    }
+
+    #[test]
+    fn observers_are_notified_in_the_order_dispatches_are_applied() {
+        let store = counting_store();
+        let notified = Arc::new(Mutex::new(vec![]));
+        let observed = notified.clone();
+        store.observe(|count| *count, move |count| observed.lock().unwrap().push(*count));
+
+        let threads: Vec<_> = (0..4).map(|_| {
+            let store = store.clone();
+            thread::spawn(move || for _ in 0..100 {
+                store.dispatch(1).unwrap();
+            })
+        }).collect();
+        threads.into_iter().for_each(|thread| thread.join().unwrap());
+
+        assert_eq!(*notified.lock().unwrap(), (1..=400).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn observers_can_dispatch_while_being_notified() {
+        let store = counting_store();
+        let notified = Arc::new(Mutex::new(vec![]));
+        let observed = notified.clone();
+        let dispatching = store.clone();
+        store.observe(|count| *count, move |count| {
+            observed.lock().unwrap().push(*count);
+            if *count < 3 {
+                dispatching.dispatch(1).unwrap();
+            }
+        });
+
+        store.dispatch(1).unwrap();
+
+        assert_eq!(*notified.lock().unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn carries_on_after_a_reducer_panics() {
+        let store = counting_store();
+        store.dispatch(1).unwrap();
+
+        let panicking = store.clone();
+        assert!(thread::spawn(move || panicking.dispatch(13)).join().is_err());
+
+        store.dispatch(1).unwrap();
+        assert_eq!(store.get_state(), 2);
+    }
+
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Task {
+        id: usize,
+    }
+
+    impl crate::Identifiable for Task {
+        type Id = usize;
+
+        fn get_id(&self) -> usize {
+            self.id
+        }
+    }
+
+    #[test]
+    fn entities_added_from_several_threads_all_land() {
+        use crate::{entity_reducer, Collection, EntityAction};
+
+        let store = SharedStore::new(Collection::new());
+        store.register_reducer(entity_reducer);
+
+        // reading the state while being notified mustn't wait on the dispatch doing the notifying.
+        // Other threads may have added more since, so it's at least the total heard.
+        let reading = store.clone();
+        let totals = Arc::new(Mutex::new(vec![]));
+        let observed = totals.clone();
+        store.observe(|tasks: &Collection<Task>| tasks.len(), move |total| {
+            assert!(reading.select(|tasks| tasks.len()) >= *total);
+            observed.lock().unwrap().push(*total);
+        });
+
+        let threads: Vec<_> = (0..4).map(|thread| {
+            let store = store.clone();
+            thread::spawn(move || for n in 0..50 {
+                store.dispatch(EntityAction::AddEntity(Task { id: thread * 50 + n })).unwrap();
+            })
+        }).collect();
+        threads.into_iter().for_each(|thread| thread.join().unwrap());
+
+        assert_eq!(store.get_state().len(), 200);
+        assert_eq!(*totals.lock().unwrap(), (1..=200).collect::<Vec<_>>());
+    }
+}
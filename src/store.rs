@@ -0,0 +1,2525 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::any::Any;
+use std::fmt::Debug;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Receiver;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+#[cfg(feature = "serde")]
+use serde::de::DeserializeOwned;
+
+#[cfg(feature = "futures")]
+use tokio::time;
+#[cfg(feature = "futures")]
+use std::time::{Duration};
+#[cfg(feature = "futures")]
+use futures::{Stream};
+#[cfg(feature = "futures")]
+use futures::channel::mpsc;
+#[cfg(feature = "futures")]
+use futures::task::{Context, Poll};
+#[cfg(feature = "futures")]
+use std::pin::Pin;
+#[cfg(feature = "futures")]
+use std::future::Future;
+#[cfg(feature = "futures")]
+use tokio::runtime::Handle;
+
+use crate::{Diffable, Error};
+use crate::dispatched::{Clock, DispatchedAction, DispatchMeta, SystemClock};
+
+// Lets try_dispatch reject an action up front, e.g. one targeting an entity that doesn't exist, with
+// the error the store's reducers fail with
+pub trait CheckedAction<State> {
+    type Error;
+
+    fn check(&self, state: &State) -> Result<(), Self::Error>;
+}
+
+// Actions reporting themselves as no-ops skip the reducers and observers entirely, once the store's
+// told to with Store::skip_noops
+pub trait IsNoop {
+    fn is_noop(&self) -> bool {
+        false
+    }
+}
+
+// A keyed action is applied once: redelivering it while its key is still in the store's window
+// (see Store::retain_idempotency_keys) is ignored
+pub trait IdempotencyKey {
+    fn idempotency_key(&self) -> Option<String> {
+        None
+    }
+}
+
+pub type Reducer<State, Action> = dyn Fn(&State, &Action) -> State;
+// A reducer that can refuse an action, e.g. one targeting an entity that doesn't exist, leaving the
+// state as it was. A store's reducers all fail with the same error, the crate's unless given.
+pub type FallibleReducer<State, Action, E = Error> = dyn Fn(&State, &Action) -> Result<State, E>;
+// Reduces one part of a state, in place
+pub type SliceReducer<State, Action, E = Error> = dyn Fn(&mut State, &Action) -> Result<(), E>;
+pub type Observer<T> = dyn Fn(&T);
+pub type ActionObserver<Action, T> = dyn Fn(&Action, &T);
+// Given each action and the state it led to, returns actions to dispatch in turn
+pub type Effect<State, Action> = dyn Fn(&DispatchedAction<Action>, &State) -> Vec<Action>;
+
+// How many effects deep a chain of actions can go before it's taken to be a cycle
+const DEFAULT_EFFECT_DEPTH_LIMIT: usize = 32;
+
+// Wraps an action observer so it only fires for the listed variants, e.g.
+// on!(TodoAction::MarkDone(..) | TodoAction::ChangeText(..) => |action, _| save(action))
+// An effect or middleware is wrapped the same way, given the action and the state or next:
+// on!(effect: TodoAction::Add(..) | TodoAction::Remove(..) => |action, state| save(action, state))
+// on!(middleware: TodoAction::Add(..) => |dispatched, next| { log(&dispatched); next.pass(dispatched) })
+// Effects return nothing for other actions, and middlewares pass them on untouched.
+#[macro_export]
+macro_rules! on {
+    (effect: $($pattern:pat)|+ => $handler:expr) => {
+        move |dispatched: &$crate::DispatchedAction<_>, state: &_| -> Vec<_> {
+            match &dispatched.action {
+                $($pattern)|+ => ($handler)(&dispatched.action, state).into_iter().collect(),
+                _ => Vec::new(),
+            }
+        }
+    };
+    (middleware: $($pattern:pat)|+ => $handler:expr) => {
+        move |dispatched: $crate::DispatchedAction<_>, next: &mut $crate::Next<'_, _, _, _>| {
+            match &dispatched.action {
+                $($pattern)|+ => ($handler)(dispatched, next),
+                _ => next.pass(dispatched),
+            }
+        }
+    };
+    ($($pattern:pat)|+ => $handler:expr) => {
+        move |action: &_, value| match action {
+            $($pattern)|+ => ($handler)(action, value),
+            _ => {},
+        }
+    };
+}
+
+pub type Selector<State, T> = dyn Fn(&State) -> T;
+
+// Runs reducer on the slice get takes out of the state, and set puts it back, e.g.
+// slice_reducer(|state: &RootState| state.todos.clone(), |state, todos| state.todos = todos, todos_reducer)
+// The slice is cloned out, so it's best as something cheap to clone such as a Collection.
+pub fn slice_reducer<State, Slice, Action, E>(
+    get: impl Fn(&State) -> Slice + 'static,
+    set: impl Fn(&mut State, Slice) + 'static,
+    reducer: impl Fn(&Slice, &Action) -> Slice + 'static,
+) -> Box<SliceReducer<State, Action, E>> {
+    try_slice_reducer(get, set, move |slice, action| Ok(reducer(slice, action)))
+}
+
+// As slice_reducer, for a reducer that can fail
+pub fn try_slice_reducer<State, Slice, Action, E>(
+    get: impl Fn(&State) -> Slice + 'static,
+    set: impl Fn(&mut State, Slice) + 'static,
+    reducer: impl Fn(&Slice, &Action) -> Result<Slice, E> + 'static,
+) -> Box<SliceReducer<State, Action, E>> {
+    Box::new(move |state, action| {
+        let slice = reducer(&get(state), action)?;
+        set(state, slice);
+        Ok(())
+    })
+}
+
+// One reducer running each slice reducer in turn on a single clone of the state. The first to fail
+// fails the lot, so no slice is left half reduced.
+pub fn combine_reducers<State, Action, E>(slices: Vec<Box<SliceReducer<State, Action, E>>>) -> Box<FallibleReducer<State, Action, E>>
+    where State: Clone + 'static, Action: 'static, E: 'static {
+    Box::new(move |state, action| {
+        let mut state = state.clone();
+        for reduce in &slices {
+            reduce(&mut state, action)?;
+        }
+        Ok(state)
+    })
+}
+
+// Returns false once the receiving end has gone away
+#[cfg(feature = "futures")]
+type StateSender<State> = dyn FnMut(&State) -> bool;
+
+// Observers are notified band by band: every Read observer (e.g. UI models) before any Effect
+// observer, and Log observers last. Within a band, registration order is kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ObserverBand {
+    Read,
+    Effect,
+    Log,
+}
+
+// An observer and its selector as a single call, given the action if there was one, so observers
+// selecting different types can share a list
+type Notify<State, Action> = dyn Fn(&State, Option<&Action>, u64);
+
+struct ObserverSelector<State, Action> {
+    band: ObserverBand,
+    notify: Box<Notify<State, Action>>,
+    subscription: SubscriptionHandle,
+    // in a row, so a single panic is forgiven once the observer gets through a notification
+    panics: usize,
+}
+
+// Told which observer panicked while being notified, and the panic's message
+pub type ObserverErrorHandler = dyn Fn(&SubscriptionHandle, &str);
+
+// Returned when registering a reducer, to unregister or replace it again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReducerHandle(usize);
+
+type RegisteredReducer<State, Action, E> = (ReducerHandle, Box<FallibleReducer<State, Action, E>>);
+
+// Why a dispatch failed, the state being as it was and observers not notified either way
+#[derive(Debug)]
+pub enum DispatchError<E = Error> {
+    // a reducer refused the action
+    Reducer { reducer: ReducerHandle, error: E },
+    // effects, middlewares or observers' dispatchers led to actions depth deep, past the limit, so
+    // likely trigger each other in a cycle. What was still queued to follow on is dropped.
+    EffectDepth { depth: usize, limit: usize },
+}
+
+// Returned when observing, to stop again with Store::unsubscribe. An observer can't reach the store
+// while being notified, so a clone moved into it can cancel instead: the observer isn't called
+// again, and is removed once the current round of notifications is done.
+#[derive(Debug, Clone)]
+pub struct SubscriptionHandle {
+    id: usize,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl SubscriptionHandle {
+    pub(crate) fn new(id: usize) -> SubscriptionHandle {
+        SubscriptionHandle { id, cancelled: Default::default() }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    // tells observers apart, e.g. when one panics
+    pub fn id(&self) -> usize {
+        self.id
+    }
+}
+
+type ActionFormatter<Action> = fn(&Action) -> String;
+
+// Keeps the last few dispatched actions, debug formatted, so a panic hook can attach them to a
+// crash report. Clones share the same buffer.
+#[derive(Clone)]
+pub struct ActionRecorder {
+    capacity: usize,
+    actions: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl ActionRecorder {
+    pub fn new(capacity: usize) -> ActionRecorder {
+        ActionRecorder { capacity, actions: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))) }
+    }
+
+    fn record(&self, action: String) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut actions = self.actions.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        if actions.len() == self.capacity {
+            actions.pop_front();
+        }
+        actions.push_back(action);
+    }
+
+    // oldest first
+    pub fn recent_actions(&self) -> Vec<String> {
+        let actions = self.actions.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        actions.iter().cloned().collect()
+    }
+}
+
+// What a Dispatcher calls on queueing an action
+type Wake = dyn Fn() + Send;
+
+// Queues actions from observers, which can't reach the store while being notified, e.g.
+// let dispatcher = store.dispatcher();
+// store.observe(all_done, move |done| if *done { dispatcher.dispatch(Celebrate) });
+// or from other threads, as it's Send. Clones share the same queue, which is dispatched in the
+// order queued: after the action of the store's next dispatch, its observers all notified, or by
+// process_pending. Deferred actions are queued separately, waiting for Store::process.
+pub struct Dispatcher<Action> {
+    queue: Arc<Mutex<VecDeque<Action>>>,
+    deferred: Arc<Mutex<VecDeque<Action>>>,
+    // told whenever an action's queued, e.g. to wake a store waiting on a thread of its own
+    wake: Arc<Mutex<Option<Box<Wake>>>>,
+}
+
+impl<Action> Clone for Dispatcher<Action> {
+    fn clone(&self) -> Self {
+        Dispatcher { queue: self.queue.clone(), deferred: self.deferred.clone(), wake: self.wake.clone() }
+    }
+}
+
+impl<Action> Dispatcher<Action> {
+    fn new() -> Dispatcher<Action> {
+        Dispatcher { queue: Default::default(), deferred: Default::default(), wake: Default::default() }
+    }
+
+    pub fn dispatch(&self, action: Action) {
+        self.queue.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push_back(action);
+
+        if let Some(wake) = &*self.wake.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) {
+            wake();
+        }
+    }
+
+    pub(crate) fn wake_with(&self, wake: impl Fn() + Send + 'static) {
+        *self.wake.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(Box::new(wake));
+    }
+
+    // as Store::dispatch_deferred
+    pub fn defer(&self, action: Action) {
+        self.deferred.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push_back(action);
+    }
+
+    fn take(&self) -> VecDeque<Action> {
+        std::mem::take(&mut *self.queue.lock().unwrap_or_else(|poisoned| poisoned.into_inner()))
+    }
+
+    fn take_deferred(&self) -> VecDeque<Action> {
+        std::mem::take(&mut *self.deferred.lock().unwrap_or_else(|poisoned| poisoned.into_inner()))
+    }
+}
+
+// Pauses and resumes a replay_timed in progress. Clones control the same replay.
+#[cfg(feature = "futures")]
+#[derive(Clone, Default)]
+pub struct ReplayControls {
+    paused: Arc<AtomicBool>,
+}
+
+#[cfg(feature = "futures")]
+impl ReplayControls {
+    pub fn new() -> ReplayControls {
+        Default::default()
+    }
+
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+}
+
+// Sits between dispatch and the reducers, seeing every action dispatched. It can pass the action on
+// (changed or not, e.g. with DispatchedAction::map) with next.pass, or swallow it by not.
+// Middlewares run in the order applied, each passing to the next, and the reducers only run once
+// the last one passes.
+pub trait Middleware<State, Action, E = Error> {
+    fn handle(&self, action: DispatchedAction<Action>, next: &mut Next<'_, State, Action, E>);
+}
+
+impl<State, Action, E, F> Middleware<State, Action, E> for F where F: Fn(DispatchedAction<Action>, &mut Next<'_, State, Action, E>) {
+    fn handle(&self, action: DispatchedAction<Action>, next: &mut Next<'_, State, Action, E>) {
+        self(action, next)
+    }
+}
+
+// Holds the store's middlewares while a dispatch passes through them, putting them back when dropped
+struct RestoreMiddlewares<'a, State, Action, E> {
+    store: &'a mut Store<State, Action, E>,
+    taken: Vec<Box<dyn Middleware<State, Action, E>>>,
+}
+
+impl<'a, State, Action, E> RestoreMiddlewares<'a, State, Action, E> {
+    fn take(store: &'a mut Store<State, Action, E>) -> Self {
+        let taken = std::mem::take(&mut store.middlewares);
+        RestoreMiddlewares { store, taken }
+    }
+}
+
+impl<'a, State, Action, E> Drop for RestoreMiddlewares<'a, State, Action, E> {
+    fn drop(&mut self) {
+        self.store.middlewares = std::mem::take(&mut self.taken);
+    }
+}
+
+// A middleware's way on through the rest of the chain
+pub struct Next<'a, State, Action, E = Error> {
+    store: &'a mut Store<State, Action, E>,
+    middlewares: &'a [Box<dyn Middleware<State, Action, E>>],
+}
+
+impl<'a, State, Action, E> Next<'a, State, Action, E> where State: Clone, Action: Clone {
+    // on to the next middleware, or the reducers after the last
+    pub fn pass(&mut self, action: DispatchedAction<Action>) {
+        match self.middlewares.split_first() {
+            Some((middleware, rest)) => middleware.handle(action, &mut Next { store: self.store, middlewares: rest }),
+            None => self.store.apply(action),
+        }
+    }
+
+    // the state so far, so after pass has returned it includes the action's changes
+    pub fn state(&self) -> &State {
+        &self.store.state
+    }
+
+    // as Store::get_state, e.g. to hold on to the state from before pass without copying it
+    pub fn get_state(&self) -> Arc<State> {
+        self.store.get_state()
+    }
+
+    // as Store::version, so after pass has returned it counts the action's changes, unless batched
+    pub fn version(&self) -> u64 {
+        self.store.version
+    }
+
+    // Dispatches a follow-up through the whole chain, once the action being handled has finished. It
+    // counts as one deeper, as an effect's would, so middlewares dispatching to each other are caught.
+    pub fn dispatch(&mut self, action: Action) {
+        self.store.follow_ups.push_back((action, self.store.effect_depth + 1, Some(self.store.transaction)));
+    }
+}
+
+// A state the store was in, and the action that led to it (None for edits, and for the state
+// history was enabled in)
+#[derive(Debug, Clone)]
+pub struct HistoryEntry<State, Action> {
+    pub action: Option<DispatchedAction<Action>>,
+    pub state: Arc<State>,
+}
+
+// What a thunk is given: the state as it is now, and dispatch
+pub struct DispatchCtx<'a, State, Action, E = Error> {
+    store: &'a mut Store<State, Action, E>,
+}
+
+impl<'a, State, Action, E> DispatchCtx<'a, State, Action, E> where State: Clone, Action: Clone {
+    // includes the changes of anything the thunk has dispatched so far
+    pub fn get_state(&self) -> Arc<State> {
+        self.store.get_state()
+    }
+
+    // as Store::dispatch, middlewares, observers and all
+    pub fn dispatch(&mut self, action: Action) -> Result<(), DispatchError<E>> {
+        self.store.dispatch(action)
+    }
+}
+
+pub struct Store<T, A, E = Error> {
+    state: Arc<T>,
+    state_changes: i32,
+    #[cfg(feature = "futures")]
+    last_stream_dispatch: i32,
+    reducers: Vec<RegisteredReducer<T, A, E>>,
+    // of the action being applied, for dispatch to return once it's through the middlewares
+    failure: Option<DispatchError<E>>,
+    next_reducer: usize,
+    middlewares: Vec<Box<dyn Middleware<T, A, E>>>,
+    effects: Vec<Box<Effect<T, A>>>,
+    effect_depth_limit: usize,
+    // how many effects led to the action being dispatched
+    effect_depth: usize,
+    // at depth, in the transaction they follow on from, or None to start one of their own
+    follow_ups: VecDeque<(A, usize, Option<u64>)>,
+    // of the action being dispatched, or the last one
+    transaction: u64,
+    transactions: u64,
+    coalesce_transactions: bool,
+    clock: Box<dyn Clock>,
+    // of the last action dispatched
+    sequence: u64,
+    // actions the reducers have taken, skipped and refused ones aside, for process to count
+    applied: u64,
+    dispatcher: Dispatcher<A>,
+    observers: Vec<ObserverSelector<T, A>>,
+    version: u64,
+    // the state as of the last version, to compare against
+    versioned_state: Arc<T>,
+    state_eq: Option<fn(&T, &T) -> bool>,
+    is_noop: Option<fn(&A) -> bool>,
+    idempotency_key: Option<fn(&A) -> Option<String>>,
+    on_observer_error: Box<ObserverErrorHandler>,
+    observer_panic_limit: usize,
+    next_subscription: usize,
+    recorders: Vec<(ActionRecorder, ActionFormatter<A>)>,
+    observers_suspended: bool,
+    notification_pending: bool,
+    pending_action: Option<A>,
+    idempotency_window: usize,
+    applied_keys: VecDeque<String>,
+    history: Vec<HistoryEntry<T, A>>,
+    history_capacity: usize,
+    // the entry the state is at, which is the last unless travelled back
+    history_position: usize,
+    #[cfg(feature = "futures")]
+    state_senders: Vec<Box<StateSender<T>>>,
+}
+
+impl<State, Action> Store<State, Action> where State: Clone, Action: Clone {
+
+    // A store with nothing registered yet; see StoreBuilder for one ready to use
+    pub fn new(state: State) -> Self {
+        Store::with_error(state)
+    }
+}
+
+impl<State, Action, E> Store<State, Action, E> where State: Clone, Action: Clone {
+
+    // As new, for reducers failing with an error of their own, e.g. Store::<_, _, ApiError>::with_error(state)
+    pub fn with_error(state: State) -> Self {
+        let state = Arc::new(state);
+
+        Store {
+            versioned_state: state.clone(),
+            version: 0,
+            state_eq: None,
+            is_noop: None,
+            idempotency_key: None,
+            state,
+            reducers: vec![],
+            next_reducer: 0,
+            failure: None,
+            middlewares: vec![],
+            effects: vec![],
+            effect_depth_limit: DEFAULT_EFFECT_DEPTH_LIMIT,
+            effect_depth: 0,
+            follow_ups: VecDeque::new(),
+            transaction: 0,
+            transactions: 0,
+            coalesce_transactions: false,
+            clock: Box::new(SystemClock),
+            sequence: 0,
+            applied: 0,
+            dispatcher: Dispatcher::new(),
+            observers: vec![],
+            on_observer_error: Box::new(|subscription, message| eprintln!("observer {} panicked: {}", subscription.id, message)),
+            observer_panic_limit: 0,
+            next_subscription: 0,
+            recorders: vec![],
+            observers_suspended: false,
+            notification_pending: false,
+            pending_action: None,
+            idempotency_window: 0,
+            applied_keys: VecDeque::new(),
+            history: vec![],
+            history_capacity: 0,
+            history_position: 0,
+            state_changes: 0,
+            #[cfg(feature = "futures")]
+            last_stream_dispatch: 0,
+            #[cfg(feature = "futures")]
+            state_senders: vec![],
+        }
+    }
+
+    pub fn register_reducer(&mut self, reducer: Box<Reducer<State, Action>>) -> ReducerHandle where State: 'static, Action: 'static {
+        self.register_fallible_reducer(Box::new(move |state, action| Ok(reducer(state, action))))
+    }
+
+    pub fn register_fallible_reducer(&mut self, reducer: Box<FallibleReducer<State, Action, E>>) -> ReducerHandle {
+        let handle = ReducerHandle(self.next_reducer);
+        self.next_reducer += 1;
+        self.reducers.push((handle, reducer));
+        handle
+    }
+
+    // in the order the reducers run
+    pub fn reducer_handles(&self) -> Vec<ReducerHandle> {
+        self.reducers.iter().map(|(handle, _)| *handle).collect()
+    }
+
+    // False if it was already unregistered
+    pub fn unregister_reducer(&mut self, handle: ReducerHandle) -> bool {
+        let before = self.reducers.len();
+        self.reducers.retain(|(registered, _)| *registered != handle);
+        self.reducers.len() < before
+    }
+
+    // Swaps in reducer where the old one ran, keeping the handle. False, leaving the reducers as
+    // they were, if the handle was unregistered.
+    pub fn replace_reducer(&mut self, handle: ReducerHandle, reducer: Box<Reducer<State, Action>>) -> bool where State: 'static, Action: 'static {
+        self.replace_fallible_reducer(handle, Box::new(move |state, action| Ok(reducer(state, action))))
+    }
+
+    pub fn replace_fallible_reducer(&mut self, handle: ReducerHandle, reducer: Box<FallibleReducer<State, Action, E>>) -> bool {
+        match self.reducers.iter_mut().find(|(registered, _)| *registered == handle) {
+            Some((_, registered)) => {
+                *registered = reducer;
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn apply_middleware(&mut self, middleware: impl Middleware<State, Action, E> + 'static) -> &mut Self {
+        self.middlewares.push(Box::new(middleware));
+        self
+    }
+
+    // Effects run after the reducers, before observers are notified, and what they return is
+    // dispatched (middlewares and all) once the action has finished. An effect can return an Option
+    // or a Vec of actions, e.g.
+    // store.register_effect(|action, _| match action { Added(id) => Some(Saved(*id)), _ => None });
+    pub fn register_effect<R: IntoIterator<Item = Action>>(&mut self, effect: impl Fn(&DispatchedAction<Action>, &State) -> R + 'static) -> &mut Self {
+        self.effects.push(Box::new(move |action, state| effect(action, state).into_iter().collect()));
+        self
+    }
+
+    // As register_effect, for effects doing async work, e.g. fetching from an API. Each future is
+    // spawned on runtime, given the action and the state it led to. What it resolves to is queued
+    // through the store's dispatcher whenever it's done, so not necessarily in the order the actions
+    // were dispatched, and dispatched along with the store's next dispatch or process_pending.
+    // Futures still running belong to the runtime, going when it does.
+    #[cfg(feature = "futures")]
+    pub fn register_async_effect<F>(&mut self, runtime: Handle, effect: impl Fn(DispatchedAction<Action>, State) -> F + 'static) -> &mut Self
+        where F: Future<Output = Option<Action>> + Send + 'static, Action: Send + 'static {
+
+        let dispatcher = self.dispatcher();
+
+        self.register_effect(move |action, state| {
+            let dispatcher = dispatcher.clone();
+            let future = effect(action.clone(), state.clone());
+            runtime.spawn(async move {
+                if let Some(action) = future.await {
+                    dispatcher.dispatch(action);
+                }
+            });
+            None
+        })
+    }
+
+    // Dispatch fails once effects, middlewares or observers' dispatchers have led to more than depth
+    // actions in a chain, as they'd otherwise cycle forever. 32 by default.
+    pub fn limit_effect_depth(&mut self, depth: usize) -> &mut Self {
+        self.effect_depth_limit = depth;
+        self
+    }
+
+    // Timestamps come from the store's clock, the system's unless set
+    pub fn set_clock(&mut self, clock: impl Clock + 'static) -> &mut Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    // Fails if a reducer refuses the action, or one of its follow-ups, the action's own failure
+    // first. Follow-ups are dispatched either way.
+    pub fn dispatch(&mut self, action: Action) -> Result<(), DispatchError<E>> {
+        self.dispatch_with_meta(action, DispatchMeta::default())
+    }
+
+    // As dispatch, with meta carried on the action for middlewares, effects and history. Follow-ups
+    // are dispatched without it.
+    pub fn dispatch_with_meta(&mut self, action: Action, meta: DispatchMeta) -> Result<(), DispatchError<E>> {
+        let dispatched = self.dispatch_at(action, meta, 0, None);
+        let follow_ups = self.dispatch_follow_ups();
+        dispatched.and(follow_ups)
+    }
+
+    // One action through the middlewares, leaving its follow-ups queued
+    fn dispatch_at(&mut self, action: Action, meta: DispatchMeta, effect_depth: usize, transaction: Option<u64>) -> Result<(), DispatchError<E>> {
+
+        if effect_depth > self.effect_depth_limit {
+            self.follow_ups.clear();
+            self.effect_depth = 0;
+            return Err(DispatchError::EffectDepth { depth: effect_depth, limit: self.effect_depth_limit });
+        }
+
+        let transaction = transaction.unwrap_or_else(|| {
+            self.transactions += 1;
+            self.transactions
+        });
+
+        // the last transaction's over, so what it held back goes out before this one changes anything
+        if transaction != self.transaction {
+            self.notify_coalesced();
+        }
+
+        self.effect_depth = effect_depth;
+        self.transaction = transaction;
+        self.sequence += 1;
+
+        let action = DispatchedAction {
+            action,
+            sequence: self.sequence,
+            transaction,
+            timestamp: self.clock.now(),
+            correlation_id: meta.correlation_id,
+        };
+
+        // taken for the length of the dispatch, as Next borrows the rest of the store, and put back
+        // by the guard even if a reducer or middleware panics
+        let middlewares = RestoreMiddlewares::take(self);
+        Next { store: &mut *middlewares.store, middlewares: &middlewares.taken }.pass(action);
+        drop(middlewares);
+
+        self.queue_dispatched(false);
+
+        match self.failure.take() {
+            Some(failure) => Err(failure),
+            None => Ok(()),
+        }
+    }
+
+    // What's been dispatched through dispatchers since. Only what observers dispatch while being
+    // notified follows on from the action, one deeper and in its transaction, others (e.g. other
+    // threads) starting chains and transactions of their own at 0, so a steady stream of them can't
+    // be taken for a cycle.
+    fn queue_dispatched(&mut self, follows_on: bool) {
+        let (depth, transaction) = match follows_on {
+            true => (self.effect_depth + 1, Some(self.transaction)),
+            false => (0, None),
+        };
+        self.follow_ups.extend(self.dispatcher.take().into_iter().map(|action| (action, depth, transaction)));
+    }
+
+    // For observers and other threads to dispatch with
+    pub fn dispatcher(&self) -> Dispatcher<Action> {
+        self.dispatcher.clone()
+    }
+
+    // Dispatches whatever's been queued through dispatchers, failing as dispatch does
+    pub fn process_pending(&mut self) -> Result<(), DispatchError<E>> {
+        self.dispatch_follow_ups()
+    }
+
+    // Dispatches what middlewares, effects and dispatchers have queued, and what that queues in
+    // turn, first in first out. A loop rather than recursion, so a long queue can't overflow the stack.
+    // A failure doesn't stop the rest, the first being returned. Edits and time travel have no way
+    // to report one, so theirs go unreported.
+    fn dispatch_follow_ups(&mut self) -> Result<(), DispatchError<E>> {
+
+        self.queue_dispatched(false);
+
+        let mut result = Ok(());
+
+        // a coalesced notification goes out once nothing's left of its transaction, and what observers
+        // dispatch on being notified is dispatched in turn
+        loop {
+            while let Some((follow_up, effect_depth, transaction)) = self.follow_ups.pop_front() {
+                let dispatched = self.dispatch_at(follow_up, DispatchMeta::default(), effect_depth, transaction);
+                result = result.and(dispatched);
+            }
+            if !self.notify_coalesced() {
+                break;
+            }
+        }
+
+        self.effect_depth = 0;
+
+        result
+    }
+
+    // Queues action for the next process, e.g. to apply everything a game loop's frame dispatched at
+    // a point of its choosing. Observers can defer through a dispatcher.
+    pub fn dispatch_deferred(&self, action: Action) {
+        self.dispatcher.defer(action);
+    }
+
+    // Dispatches what's been deferred, first in first out, observers being notified once for the
+    // lot. What that defers in turn is dispatched after, in as many rounds as it takes, up to the
+    // effect depth limit; anything deferred past that waits for the next process. Returns how many
+    // of the deferred actions the reducers took, leaving out those refused, dropped by a middleware or
+    // skipped as no-ops or repeats. What follows on from an action isn't counted, nor does its
+    // failing stop the action counting.
+    pub fn process(&mut self) -> usize {
+
+        let mut applied = 0;
+
+        for _ in 0..=self.effect_depth_limit {
+            let deferred = self.dispatcher.take_deferred();
+            if deferred.is_empty() {
+                break;
+            }
+
+            self.batch(|store| for action in deferred {
+                let before = store.applied;
+                // the action by itself, then what follows on from it, as dispatch would
+                let _ = store.dispatch_at(action, DispatchMeta::default(), 0, None);
+                if store.applied > before {
+                    applied += 1;
+                }
+                let _ = store.dispatch_follow_ups();
+            });
+        }
+
+        applied
+    }
+
+    // For logic spanning several dispatches, e.g. dispatching depending on the state so far. Nothing
+    // else can dispatch until the thunk returns.
+    pub fn dispatch_thunk<R>(&mut self, thunk: impl FnOnce(&mut DispatchCtx<'_, State, Action, E>) -> R) -> R {
+        thunk(&mut DispatchCtx { store: self })
+    }
+
+    // what dispatch does once through the middlewares
+    fn apply(&mut self, dispatched: DispatchedAction<Action>) {
+        if self.is_noop.is_some_and(|is_noop| is_noop(&dispatched.action)) {
+            return;
+        }
+
+        let key = self.idempotency_key.and_then(|key| key(&dispatched.action));
+        if let Some(key) = &key {
+            if self.applied_keys.contains(key) {
+                return;
+            }
+        }
+
+        // recorded up front so an action that panics or fails a reducer still makes it into the report
+        self.recorders.iter().for_each(|(recorder, format)| recorder.record(format(&dispatched.action)));
+
+        // only replaced once every reducer has succeeded
+        match self.reduce(&self.state, &dispatched.action) {
+            Ok(Some(state)) => self.state = Arc::new(state),
+            Ok(None) => {},
+            Err(failure) => {
+                self.failure = Some(failure);
+                return;
+            },
+        }
+        self.applied += 1;
+
+        // only once applied, so a failed action can be retried
+        if let Some(key) = key {
+            if self.idempotency_window > 0 {
+                if self.applied_keys.len() == self.idempotency_window {
+                    self.applied_keys.pop_front();
+                }
+                self.applied_keys.push_back(key);
+            }
+        }
+
+        self.record_history(Some(dispatched.clone()));
+        self.run_effects(&dispatched);
+
+        self.notify(Some(dispatched.action));
+    }
+
+    // Each reducer returns a new state from the last, so state is never cloned. None without reducers.
+    fn reduce(&self, state: &State, action: &Action) -> Result<Option<State>, DispatchError<E>> {
+        let mut reduced = None;
+        for (handle, reducer) in &self.reducers {
+            match reducer(reduced.as_ref().unwrap_or(state), action) {
+                Ok(state) => reduced = Some(state),
+                Err(error) => return Err(DispatchError::Reducer { reducer: *handle, error }),
+            }
+        }
+        Ok(reduced)
+    }
+
+    // The state the reducers make of initial and actions, e.g. to rebuild one from an event log or
+    // reproduce a bug. Only the reducers run: no middlewares, effects or observers, and the store's
+    // own state is left as is. Fails at the first action refused.
+    pub fn replay(&self, initial: State, actions: impl IntoIterator<Item = Action>) -> Result<State, DispatchError<E>> {
+        let is_noop = self.is_noop.unwrap_or(|_| false);
+        actions.into_iter().filter(|action| !is_noop(action)).try_fold(initial, |state, action| {
+            Ok(self.reduce(&state, &action)?.unwrap_or(state))
+        })
+    }
+
+    // As replay, making the result the store's state as an edit would, unless an action's refused
+    pub fn replay_and_commit(&mut self, initial: State, actions: impl IntoIterator<Item = Action>) -> Result<(), DispatchError<E>> {
+        let replayed = self.replay(initial, actions)?;
+        self.state = Arc::new(replayed);
+        self.record_history(None);
+        self.notify(None);
+        let _ = self.dispatch_follow_ups();
+        Ok(())
+    }
+
+    fn run_effects(&mut self, action: &DispatchedAction<Action>) {
+
+        let depth = self.effect_depth + 1;
+        let transaction = Some(self.transaction);
+        let state = &self.state;
+        let actions: Vec<Action> = self.effects.iter().flat_map(|effect| effect(action, state)).collect();
+
+        self.follow_ups.extend(actions.into_iter().map(|action| (action, depth, transaction)));
+    }
+
+    // Escape hatch for imperative bulk edits: f mutates a working copy which is then committed with
+    // a single notification. Reducers and action recorders are bypassed.
+    pub fn edit(&mut self, f: impl FnOnce(&mut State)) {
+        let mut working = State::clone(&self.state);
+        f(&mut working);
+        self.state = Arc::new(working);
+        self.record_history(None);
+        self.notify(None);
+        let _ = self.dispatch_follow_ups();
+    }
+
+    // As edit, but the working copy is discarded, and nobody notified, if f fails
+    pub fn try_edit<F>(&mut self, f: impl FnOnce(&mut State) -> Result<(), F>) -> Result<(), F> {
+        let mut working = State::clone(&self.state);
+        f(&mut working)?;
+        self.state = Arc::new(working);
+        self.record_history(None);
+        self.notify(None);
+        let _ = self.dispatch_follow_ups();
+        Ok(())
+    }
+
+    // Keeps the last capacity states (at least 1, the current one) with the actions leading to them,
+    // to travel back and forth between. Each is a snapshot as get_state takes, so cheap to keep.
+    pub fn enable_history(&mut self, capacity: usize) {
+        self.history_capacity = capacity.max(1);
+        self.history = vec![HistoryEntry { action: None, state: self.state.clone() }];
+        self.history_position = 0;
+    }
+
+    // oldest first
+    pub fn history(&self) -> &[HistoryEntry<State, Action>] {
+        &self.history
+    }
+
+    pub fn history_position(&self) -> usize {
+        self.history_position
+    }
+
+    // Sets the state to the one at index in history, notifying observers as an edit does. Reducers
+    // and effects don't run. False if there's no such entry.
+    pub fn jump_to(&mut self, index: usize) -> bool {
+
+        let state = match self.history.get(index) {
+            Some(entry) => entry.state.clone(),
+            None => return false,
+        };
+
+        self.history_position = index;
+        self.state = state;
+        self.notify(None);
+        let _ = self.dispatch_follow_ups();
+        true
+    }
+
+    pub fn step_back(&mut self) -> bool {
+        self.history_position > 0 && self.jump_to(self.history_position - 1)
+    }
+
+    pub fn step_forward(&mut self) -> bool {
+        self.jump_to(self.history_position + 1)
+    }
+
+    // Dispatching or editing while travelled back drops the entries after it, as they no longer follow
+    fn record_history(&mut self, action: Option<DispatchedAction<Action>>) {
+
+        if self.history_capacity == 0 {
+            return;
+        }
+
+        self.history.truncate(self.history_position + 1);
+        self.history.push(HistoryEntry { action, state: self.state.clone() });
+
+        if self.history.len() > self.history_capacity {
+            self.history.remove(0);
+        }
+
+        self.history_position = self.history.len() - 1;
+    }
+
+    fn notify(&mut self, action: Option<Action>) {
+        self.state_changes += 1;
+
+        if self.observers_suspended || self.coalesce_transactions {
+            self.notification_pending = true;
+            if action.is_some() {
+                self.pending_action = action;
+            }
+            return;
+        }
+
+        self.bump_version();
+        self.notify_observers(action.as_ref());
+    }
+
+    // Once per notification, so a batch counts as one change
+    fn bump_version(&mut self) {
+        let changed = match self.state_eq {
+            Some(eq) => !eq(&self.versioned_state, &self.state),
+            None => true,
+        };
+
+        if changed {
+            self.version += 1;
+            self.versioned_state = self.state.clone();
+        }
+    }
+
+    // A panicking observer doesn't stop the rest being notified. Observers can't change the state, so
+    // it can't be left half changed, and whatever the observer's own captures are left as is up to it.
+    fn notify_observers(&mut self, action: Option<&Action>) {
+        let state = &self.state;
+        let version = self.version;
+
+        for so in self.observers.iter_mut().filter(|so| !so.subscription.is_cancelled()) {
+            match panic::catch_unwind(AssertUnwindSafe(|| (so.notify)(state, action, version))) {
+                Ok(()) => so.panics = 0,
+                Err(payload) => {
+                    so.panics += 1;
+                    (self.on_observer_error)(&so.subscription, panic_message(&*payload));
+                    if so.panics == self.observer_panic_limit {
+                        so.subscription.cancel();
+                    }
+                },
+            }
+        }
+
+        self.observers.retain(|so| !so.subscription.is_cancelled());
+        self.queue_dispatched(true);
+
+        #[cfg(feature = "futures")]
+        {
+            let state = &self.state;
+            self.state_senders.retain_mut(|send| send(state));
+        }
+    }
+
+    // Remembers the keys of the last window keyed actions, ignoring redeliveries of them. Off (0) by
+    // default, so actions needn't be IdempotencyKey.
+    pub fn retain_idempotency_keys(&mut self, window: usize) where Action: IdempotencyKey {
+        self.idempotency_key = Some(Action::idempotency_key);
+        self.idempotency_window = window;
+        while self.applied_keys.len() > window {
+            self.applied_keys.pop_front();
+        }
+    }
+
+    // Until resumed, dispatches update state without notifying anyone
+    pub fn suspend_observers(&mut self) {
+        self.observers_suspended = true;
+    }
+
+    // Notifies once with the current state if anything was dispatched while suspended. Action
+    // observers are handed the last action dispatched in that time.
+    pub fn resume_observers(&mut self) {
+        self.observers_suspended = false;
+
+        if self.notification_pending {
+            self.notify_pending();
+            let _ = self.dispatch_follow_ups();
+        }
+    }
+
+    fn notify_pending(&mut self) {
+        self.notification_pending = false;
+        let action = self.pending_action.take();
+        self.bump_version();
+        self.notify_observers(action.as_ref());
+    }
+
+    // Observers are notified once per transaction: an action dispatched from outside the store, and
+    // the follow-ups its effects, middlewares and observers dispatch, e.g. so a save and the
+    // confirmation its effect dispatches are one UI update. Action observers are handed the last
+    // action. Actions carry their transaction's id either way.
+    pub fn coalesce_transactions(&mut self) -> &mut Self {
+        self.coalesce_transactions = true;
+        self
+    }
+
+    // Sends the notification held back for the transaction just finished. False if there was none.
+    fn notify_coalesced(&mut self) -> bool {
+        if !self.coalesce_transactions || self.observers_suspended || !self.notification_pending {
+            return false;
+        }
+        self.notify_pending();
+        true
+    }
+
+    // Dispatches each action in turn, then notifies observers once with the final state. A failed
+    // action doesn't stop the rest, the first failure being returned.
+    pub fn dispatch_all(&mut self, actions: impl IntoIterator<Item = Action>) -> Result<(), DispatchError<E>> {
+        let mut result = Ok(());
+        self.batch(|store| for action in actions {
+            let dispatched = store.dispatch(action);
+            if result.is_ok() {
+                result = dispatched;
+            }
+        });
+        result
+    }
+
+    // Observers are notified once, after f, of whatever it dispatched. Reducers still see every
+    // action as it's dispatched. Batches can nest, the outermost doing the notifying.
+    pub fn batch(&mut self, f: impl FnOnce(&mut Self)) {
+        let already_suspended = self.observers_suspended;
+        self.suspend_observers();
+        f(self);
+        if !already_suspended {
+            self.resume_observers();
+        }
+    }
+
+    // Like dispatch, but refuses actions that fail their check instead of letting reducers ignore them
+    pub fn try_dispatch(&mut self, action: Action) -> Result<(), E> where Action: CheckedAction<State, Error = E>, E: From<DispatchError<E>> {
+        action.check(&self.state)?;
+        self.dispatch(action).map_err(E::from)
+    }
+
+    // e.g. for a REPL or websocket control channel
+    #[cfg(feature = "serde")]
+    pub fn dispatch_json(&mut self, json: &str) -> Result<(), Error> where Action: DeserializeOwned, Error: From<DispatchError<E>> {
+        let action = serde_json::from_str(json).map_err(Error::Json)?;
+        self.dispatch(action).map_err(Error::from)
+    }
+
+    // The state as JSON, e.g. to save and later hydrate from
+    #[cfg(feature = "serde")]
+    pub fn snapshot(&self) -> Result<String, Error> where State: Serialize {
+        serde_json::to_string(&*self.state).map_err(Error::Json)
+    }
+
+    // Replaces the state with one from JSON, notifying observers as an edit does. Invalid JSON leaves
+    // the state as it was.
+    #[cfg(feature = "serde")]
+    pub fn hydrate(&mut self, json: &str) -> Result<(), Error> where State: DeserializeOwned {
+        let hydrated = serde_json::from_str(json).map_err(Error::Json)?;
+        self.edit(|state| *state = hydrated);
+        Ok(())
+    }
+
+    // Hydrates from a file PersistMiddleware wrote. A missing file, as on a first run, keeps the state
+    // as it is, as does one that can't be read or hydrated from, which is an error.
+    #[cfg(feature = "serde")]
+    pub fn rehydrate_from(&mut self, path: impl AsRef<std::path::Path>) -> Result<(), Error> where State: DeserializeOwned {
+        match std::fs::read_to_string(path) {
+            Ok(json) => self.hydrate(&json),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(error) => Err(Error::Io(error)),
+        }
+    }
+
+    // Dispatches each action in turn, waiting interval between them, so observers see the state
+    // evolve as it did live. While paused, the pause is re-checked every interval. Stops at the first
+    // failure, as what follows no longer replays what happened.
+    #[cfg(feature = "futures")]
+    pub async fn replay_timed(&mut self, actions: &[Action], interval: Duration, controls: &ReplayControls) -> Result<(), DispatchError<E>> {
+        for (i, action) in actions.iter().enumerate() {
+            if i > 0 {
+                time::delay_for(interval).await;
+            }
+            while controls.is_paused() {
+                time::delay_for(interval).await;
+            }
+            self.dispatch(action.clone())?;
+        }
+        Ok(())
+    }
+
+    pub fn record_actions(&mut self, capacity: usize) -> ActionRecorder where Action: Debug {
+        let recorder = ActionRecorder::new(capacity);
+        self.recorders.push((recorder.clone(), |action| format!("{:?}", action)));
+        recorder
+    }
+
+    // Goes up with each change observers are notified of, so a batch counts once, and failed or
+    // no-op actions don't count. By default every change counts, even one leaving the state equal to
+    // what it was; see version_by_equality.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    // Dispatching an action that's a no-op then skips the reducers and observers, and doesn't count
+    // as a change. Off by default, so actions needn't be IsNoop.
+    pub fn skip_noops(&mut self) -> &mut Self where Action: IsNoop {
+        self.is_noop = Some(Action::is_noop);
+        self
+    }
+
+    // Only counts changes leaving the state unequal to what it was at the last one counted, e.g. a
+    // batch whose actions cancel out doesn't count. Comparing is cheap for persistent parts such as
+    // Collections, which are equal by pointer when unchanged.
+    pub fn version_by_equality(&mut self) -> &mut Self where State: PartialEq {
+        self.state_eq = Some(State::eq);
+        self
+    }
+
+    pub(crate) fn state_changes(&self) -> i32 {
+        self.state_changes
+    }
+
+    // Drops what's left of a dispatch a panic cut short, e.g. before reusing the store from a
+    // poisoned lock. Its follow-ups followed on from an action that may not have been applied, so
+    // they go too. The middlewares were put back as the panic unwound.
+    pub(crate) fn abandon_dispatch(&mut self) {
+        self.follow_ups.clear();
+        self.failure = None;
+        self.effect_depth = 0;
+    }
+
+    // A snapshot, cheap to take and unchanged by later dispatches, which replace the store's
+    // rather than change it
+    pub fn get_state(&self) -> Arc<State> {
+        self.state.clone()
+    }
+
+    // For pull-based consumers that can't subscribe: they hold on to a snapshot and ask what's
+    // changed since, without the store tracking anything per consumer
+    pub fn changes_since(&self, prev: &State) -> State::Diff where State: Diffable {
+        self.state.diff(prev)
+    }
+
+    pub fn select<T>(&self, selector: Box<Selector<State, T>>) -> T {
+        selector(&self.state)
+    }
+
+    // observer is handed whatever selector picks out of the state, of any type
+    pub fn observe<S: 'static>(&mut self, selector: impl Fn(&State) -> S + 'static, observer: impl Fn(&S) + 'static) -> SubscriptionHandle {
+        self.observe_in(ObserverBand::Read, selector, observer)
+    }
+
+    pub fn observe_in<S: 'static>(&mut self, band: ObserverBand, selector: impl Fn(&State) -> S + 'static, observer: impl Fn(&S) + 'static) -> SubscriptionHandle {
+        self.insert_observer(band, Box::new(move |state, _, _| observer(&selector(state))))
+    }
+
+    // As observe, also handing observer the version the state's at
+    pub fn observe_with_version<S: 'static>(&mut self, selector: impl Fn(&State) -> S + 'static, observer: impl Fn(u64, &S) + 'static) -> SubscriptionHandle {
+        self.observe_with_version_in(ObserverBand::Read, selector, observer)
+    }
+
+    pub fn observe_with_version_in<S: 'static>(&mut self, band: ObserverBand, selector: impl Fn(&State) -> S + 'static, observer: impl Fn(u64, &S) + 'static) -> SubscriptionHandle {
+        self.insert_observer(band, Box::new(move |state, _, version| observer(version, &selector(state))))
+    }
+
+    // observer is handed the whole state after every change, e.g. for a renderer doing its own diffing
+    pub fn subscribe(&mut self, observer: impl Fn(&State) + 'static) -> SubscriptionHandle {
+        self.insert_observer(ObserverBand::Read, Box::new(move |state, _, _| observer(state)))
+    }
+
+    // As observe, but only when the selection differs from the one last handed to observer. The
+    // first notification always goes through.
+    pub fn observe_distinct<S: PartialEq + 'static>(&mut self, selector: impl Fn(&State) -> S + 'static, observer: impl Fn(&S) + 'static) -> SubscriptionHandle {
+        self.observe_distinct_in(ObserverBand::Read, selector, observer)
+    }
+
+    pub fn observe_distinct_in<S: PartialEq + 'static>(&mut self, band: ObserverBand, selector: impl Fn(&State) -> S + 'static, observer: impl Fn(&S) + 'static) -> SubscriptionHandle {
+        self.observe_with_prev_in(band, selector, move |_, selected| observer(selected))
+    }
+
+    // As observe_distinct, also handing observer the selection it was last given (None the first
+    // time), e.g. for transition animations
+    pub fn observe_with_prev<S: PartialEq + 'static>(&mut self, selector: impl Fn(&State) -> S + 'static, observer: impl Fn(Option<&S>, &S) + 'static) -> SubscriptionHandle {
+        self.observe_with_prev_in(ObserverBand::Read, selector, observer)
+    }
+
+    pub fn observe_with_prev_in<S: PartialEq + 'static>(&mut self, band: ObserverBand, selector: impl Fn(&State) -> S + 'static, observer: impl Fn(Option<&S>, &S) + 'static) -> SubscriptionHandle {
+
+        let last = RefCell::new(None);
+
+        self.insert_observer(band, Box::new(move |state, _, _| {
+            let selected = selector(state);
+            if last.borrow().as_ref() != Some(&selected) {
+                observer(last.borrow().as_ref(), &selected);
+                last.replace(Some(selected));
+            }
+        }))
+    }
+
+    // As observe, with the action that caused the change, e.g. to animate a removal differently.
+    // Only called for changes made by an action, so edit & try_edit don't reach these.
+    pub fn observe_with_action<S: 'static>(&mut self, selector: impl Fn(&State) -> S + 'static, observer: impl Fn(&Action, &S) + 'static) -> SubscriptionHandle {
+        self.observe_with_action_in(ObserverBand::Read, selector, observer)
+    }
+
+    pub fn observe_with_action_in<S: 'static>(&mut self, band: ObserverBand, selector: impl Fn(&State) -> S + 'static, observer: impl Fn(&Action, &S) + 'static) -> SubscriptionHandle {
+        self.insert_observer(band, Box::new(move |state, action, _| {
+            if let Some(action) = action {
+                observer(action, &selector(state))
+            }
+        }))
+    }
+
+    // Sends what selector picks out after each change into the channel, for another thread to react
+    // to at its own pace. Once the receiver's dropped the observer removes itself, on its next send.
+    pub fn observe_channel<S: Send + 'static>(&mut self, selector: impl Fn(&State) -> S + 'static) -> Receiver<S> {
+        self.observe_channel_where(move |state| Some(selector(state)))
+    }
+
+    // As observe_channel, skipping selections equal to the last sent
+    pub fn observe_channel_distinct<S: PartialEq + Clone + Send + 'static>(&mut self, selector: impl Fn(&State) -> S + 'static) -> Receiver<S> {
+
+        let distinct = Distinct::new();
+        self.observe_channel_where(move |state| distinct.changed(selector(state)))
+    }
+
+    // sends the selections that aren't None
+    fn observe_channel_where<S: Send + 'static>(&mut self, selector: impl Fn(&State) -> Option<S> + 'static) -> Receiver<S> {
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let subscription = self.new_subscription();
+        let disconnected = subscription.clone();
+
+        self.insert_subscription(ObserverBand::Read, subscription, Box::new(move |state, _, _| {
+            if let Some(selected) = selector(state) {
+                if sender.send(selected).is_err() {
+                    disconnected.cancel();
+                }
+            }
+        }));
+
+        receiver
+    }
+
+    // As observe_channel_distinct, as a Stream. Once it's dropped the observer removes itself, on its
+    // next send.
+    #[cfg(feature = "futures")]
+    pub fn observe_stream<S: PartialEq + Clone + 'static>(&mut self, selector: impl Fn(&State) -> S + 'static) -> impl Stream<Item = S> {
+
+        let (sender, receiver) = mpsc::unbounded();
+        let subscription = self.new_subscription();
+        let disconnected = subscription.clone();
+        let distinct = Distinct::new();
+
+        self.insert_subscription(ObserverBand::Read, subscription, Box::new(move |state, _, _| {
+            if let Some(selected) = distinct.changed(selector(state)) {
+                if sender.unbounded_send(selected).is_err() {
+                    disconnected.cancel();
+                }
+            }
+        }));
+
+        receiver
+    }
+
+    // Called with each observer that panics, instead of printing it to stderr
+    pub fn on_observer_error(&mut self, handler: impl Fn(&SubscriptionHandle, &str) + 'static) -> &mut Self {
+        self.on_observer_error = Box::new(handler);
+        self
+    }
+
+    // Unsubscribes an observer once it's panicked panics notifications in a row. Never by default (0).
+    pub fn limit_observer_panics(&mut self, panics: usize) -> &mut Self {
+        self.observer_panic_limit = panics;
+        self
+    }
+
+    fn new_subscription(&mut self) -> SubscriptionHandle {
+        let subscription = SubscriptionHandle::new(self.next_subscription);
+        self.next_subscription += 1;
+        subscription
+    }
+
+    fn insert_observer(&mut self, band: ObserverBand, notify: Box<Notify<State, Action>>) -> SubscriptionHandle {
+        let subscription = self.new_subscription();
+        self.insert_subscription(band, subscription.clone(), notify);
+        subscription
+    }
+
+    fn insert_subscription(&mut self, band: ObserverBand, subscription: SubscriptionHandle, notify: Box<Notify<State, Action>>) {
+
+        // the list is kept sorted by band, so dispatch can notify in a single pass
+        let position = self.observers.iter().position(|so| so.band > band).unwrap_or(self.observers.len());
+        self.observers.insert(position, ObserverSelector { band, notify, subscription, panics: 0 });
+    }
+
+    // Stops the observer being notified. Unsubscribing twice, or after a cancel, does nothing.
+    pub fn unsubscribe(&mut self, subscription: SubscriptionHandle) {
+        self.observers.retain(|so| so.subscription.id != subscription.id);
+    }
+
+    // Yields the state after every dispatch that changed it; consecutive equal states are skipped.
+    // The stream ends when the store is dropped, as the sending half is owned by the store.
+    #[cfg(feature = "futures")]
+    pub fn subscribe_async(&mut self) -> impl Stream<Item = State> where State: PartialEq + 'static {
+        let (sender, receiver) = mpsc::unbounded();
+        let mut last: Option<State> = None;
+
+        self.state_senders.push(Box::new(move |state: &State| {
+            if last.as_ref() == Some(state) {
+                return true;
+            }
+            last = Some(state.clone());
+            sender.unbounded_send(state.clone()).is_ok()
+        }));
+
+        receiver
+    }
+}
+
+// panics carry a &str or String, unless raised with panic_any
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => message,
+        None => payload.downcast_ref::<String>().map_or("no message", String::as_str),
+    }
+}
+
+// Remembers the last selection, to skip those equal to it
+struct Distinct<S> {
+    last: RefCell<Option<S>>,
+}
+
+impl<S: PartialEq + Clone> Distinct<S> {
+    fn new() -> Distinct<S> {
+        Distinct { last: RefCell::new(None) }
+    }
+
+    // None if selected is equal to the last
+    fn changed(&self, selected: S) -> Option<S> {
+        if self.last.borrow().as_ref() == Some(&selected) {
+            return None;
+        }
+        self.last.replace(Some(selected.clone()));
+        Some(selected)
+    }
+}
+
+impl<S, A> Unpin for Store<S, A> {}
+
+#[cfg(feature = "futures")]
+impl<S, A> Stream for Store<S, A> where S: Clone {
+    type Item = S;
+
+    fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+
+        if self.last_stream_dispatch != self.state_changes {
+            return Poll::Pending;
+        }
+
+        self.last_stream_dispatch = self.state_changes;
+        Poll::Ready(Some(S::clone(&self.state)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "serde")]
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Clone, PartialEq)]
+    #[cfg_attr(feature = "serde", derive(Deserialize))]
+    enum Counter {
+        Add(i32),
+        Ping,
+        Pong,
+    }
+
+    impl IsNoop for Counter {
+        fn is_noop(&self) -> bool {
+            *self == Counter::Add(0)
+        }
+    }
+
+    impl IdempotencyKey for Counter {
+        fn idempotency_key(&self) -> Option<String> {
+            match self {
+                Counter::Add(n) => Some(format!("add {}", n)),
+                _ => None,
+            }
+        }
+    }
+
+    fn counting_store() -> Store<i32, Counter> {
+        let mut store = Store::new(0);
+        store.register_reducer(Box::new(|count, action| match action {
+            Counter::Add(n) => count + n,
+            Counter::Ping | Counter::Pong => *count,
+        }));
+        store
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum CounterError {
+        Negative,
+        TooBig,
+        Cycle,
+    }
+
+    impl From<DispatchError<CounterError>> for CounterError {
+        fn from(failure: DispatchError<CounterError>) -> Self {
+            match failure {
+                DispatchError::Reducer { error, .. } => error,
+                DispatchError::EffectDepth { .. } => CounterError::Cycle,
+            }
+        }
+    }
+
+    impl CheckedAction<i32> for Counter {
+        type Error = CounterError;
+
+        fn check(&self, _: &i32) -> Result<(), CounterError> {
+            match self {
+                Counter::Add(n) if *n < 0 => Err(CounterError::Negative),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    #[test]
+    fn reducers_and_checks_fail_with_an_error_of_their_own() {
+        let mut store = Store::<i32, Counter, CounterError>::with_error(0);
+        store.register_fallible_reducer(Box::new(|count, action| match action {
+            Counter::Add(n) if count + n > 100 => Err(CounterError::TooBig),
+            Counter::Add(n) => Ok(count + n),
+            _ => Ok(*count),
+        }));
+
+        assert_eq!(store.try_dispatch(Counter::Add(-1)), Err(CounterError::Negative));
+        assert_eq!(store.try_dispatch(Counter::Add(101)), Err(CounterError::TooBig));
+        assert_eq!(store.try_dispatch(Counter::Add(100)), Ok(()));
+        assert_eq!(*store.get_state(), 100);
+    }
+
+    #[test]
+    fn noops_skip_the_reducers_once_asked_to() {
+        let mut store = counting_store();
+        let calls = Arc::new(Mutex::new(0));
+        let counted = calls.clone();
+        store.register_reducer(Box::new(move |count, _| {
+            *counted.lock().unwrap() += 1;
+            *count
+        }));
+
+        store.dispatch(Counter::Add(0)).unwrap();
+        assert_eq!((*calls.lock().unwrap(), store.version()), (1, 1));
+
+        store.skip_noops();
+        store.dispatch(Counter::Add(0)).unwrap();
+        assert_eq!((*calls.lock().unwrap(), store.version()), (1, 1));
+    }
+
+    #[test]
+    fn keyed_actions_are_applied_once() {
+        let mut store = counting_store();
+
+        store.dispatch(Counter::Add(5)).unwrap();
+        store.dispatch(Counter::Add(5)).unwrap();
+        assert_eq!(*store.get_state(), 10);
+
+        store.retain_idempotency_keys(8);
+        store.dispatch(Counter::Add(5)).unwrap();
+        store.dispatch(Counter::Add(5)).unwrap();
+        store.dispatch(Counter::Add(6)).unwrap();
+        assert_eq!(*store.get_state(), 21);
+    }
+
+    #[test]
+    fn dispatches_from_elsewhere_start_chains_of_their_own() {
+        let mut store = counting_store();
+        store.limit_effect_depth(2);
+
+        // as another thread would, queueing another action during each dispatch
+        let dispatcher = store.dispatcher();
+        store.apply_middleware(move |action, next: &mut Next<'_, i32, Counter>| {
+            next.pass(action);
+            if *next.state() < 10 {
+                dispatcher.dispatch(Counter::Add(1));
+            }
+        });
+
+        store.dispatch(Counter::Add(1)).unwrap();
+
+        assert_eq!(*store.get_state(), 10);
+    }
+
+    #[test]
+    fn middlewares_dispatching_to_each_other_are_caught() {
+        let mut store = counting_store();
+        store.apply_middleware(|action: DispatchedAction<Counter>, next: &mut Next<'_, i32, Counter>| {
+            if action.action == Counter::Ping {
+                next.dispatch(Counter::Pong);
+            }
+            next.pass(action);
+        });
+        store.apply_middleware(|action: DispatchedAction<Counter>, next: &mut Next<'_, i32, Counter>| {
+            if action.action == Counter::Pong {
+                next.dispatch(Counter::Ping);
+            }
+            next.pass(action);
+        });
+
+        match store.dispatch(Counter::Ping) {
+            Err(DispatchError::EffectDepth { depth, limit }) => assert_eq!((depth, limit), (33, 32)),
+            result => panic!("expected the cycle to be caught, got {:?}", result),
+        }
+
+        // with nothing left queued, so the store carries on as before
+        store.dispatch(Counter::Add(1)).unwrap();
+        assert_eq!(*store.get_state(), 1);
+    }
+
+    #[test]
+    fn transactions_are_notified_once_coalesced() {
+        let mut store = counting_store();
+        store.coalesce_transactions();
+        store.register_effect(|action, _| match action.action {
+            Counter::Ping => Some(Counter::Add(1)),
+            _ => None,
+        });
+
+        let transactions = Arc::new(Mutex::new(vec![]));
+        let recorded = transactions.clone();
+        store.apply_middleware(move |action: DispatchedAction<Counter>, next: &mut Next<'_, i32, Counter>| {
+            recorded.lock().unwrap().push(action.transaction);
+            next.pass(action);
+        });
+
+        let notified = Arc::new(Mutex::new(vec![]));
+        let observed = notified.clone();
+        store.subscribe(move |count| observed.lock().unwrap().push(*count));
+
+        store.dispatch(Counter::Ping).unwrap();
+        store.dispatch(Counter::Ping).unwrap();
+
+        assert_eq!(*transactions.lock().unwrap(), vec![1, 1, 2, 2]);
+        assert_eq!(*notified.lock().unwrap(), vec![1, 2]);
+        assert_eq!(store.version(), 2);
+    }
+
+    #[test]
+    fn effects_and_middlewares_can_match_several_variants() {
+        let mut store = counting_store();
+        store.register_effect(on!(effect: Counter::Ping | Counter::Pong => |_, count: &i32| Some(Counter::Add(*count + 1))));
+        store.apply_middleware(on!(middleware: Counter::Add(10..=99) => |_, _| {}));
+
+        store.dispatch(Counter::Ping).unwrap();
+        store.dispatch(Counter::Pong).unwrap();
+        assert_eq!(*store.get_state(), 3);
+
+        // the middleware drops the first, passing the second, and its follow-up, on untouched
+        store.dispatch(Counter::Add(10)).unwrap();
+        store.dispatch(Counter::Ping).unwrap();
+        assert_eq!(*store.get_state(), 7);
+    }
+
+    #[test]
+    fn middlewares_survive_a_panicking_reducer() {
+        let mut store = counting_store();
+        store.register_reducer(Box::new(|count, action| match action {
+            Counter::Add(26) => panic!("unlucky"),
+            _ => *count,
+        }));
+        store.apply_middleware(|mut action: DispatchedAction<Counter>, next: &mut Next<'_, i32, Counter>| {
+            if let Counter::Add(n) = action.action {
+                action.action = Counter::Add(n * 2);
+            }
+            next.pass(action);
+        });
+
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| store.dispatch(Counter::Add(13))));
+        assert!(panicked.is_err());
+
+        store.dispatch(Counter::Add(1)).unwrap();
+        assert_eq!(*store.get_state(), 2);
+    }
+
+    #[cfg(feature = "futures")]
+    #[test]
+    fn the_state_streams_once_per_change_until_the_store_goes() {
+        use futures::StreamExt;
+
+        let mut store = counting_store();
+        let states = store.subscribe_async();
+
+        store.dispatch(Counter::Add(1)).unwrap();
+        store.dispatch(Counter::Ping).unwrap();
+        store.dispatch(Counter::Add(2)).unwrap();
+        drop(store);
+
+        assert_eq!(futures::executor::block_on(states.collect::<Vec<_>>()), vec![1, 3]);
+    }
+
+    #[test]
+    fn observers_run_band_by_band_in_registration_order() {
+        let mut store = counting_store();
+        let order = Arc::new(Mutex::new(vec![]));
+        let observer = |name: &'static str| {
+            let order = order.clone();
+            move |_: &i32| order.lock().unwrap().push(name)
+        };
+
+        store.observe_in(ObserverBand::Log, |count| *count, observer("log"));
+        store.observe_in(ObserverBand::Effect, |count| *count, observer("first effect"));
+        store.observe_in(ObserverBand::Read, |count| *count, observer("first read"));
+        store.observe_in(ObserverBand::Effect, |count| *count, observer("second effect"));
+        store.observe(|count| *count, observer("second read"));
+
+        store.dispatch(Counter::Add(1)).unwrap();
+
+        assert_eq!(*order.lock().unwrap(), vec!["first read", "second read", "first effect", "second effect", "log"]);
+    }
+
+    #[test]
+    fn only_the_last_few_actions_are_recorded() {
+        let mut store = counting_store();
+        let recorder = store.record_actions(3);
+
+        (1..=5).for_each(|n| store.dispatch(Counter::Add(n)).unwrap());
+
+        assert_eq!(recorder.recent_actions(), vec!["Add(3)", "Add(4)", "Add(5)"]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn actions_can_be_dispatched_as_json() {
+        let mut store = counting_store();
+
+        store.dispatch_json(r#"{"Add": 3}"#).unwrap();
+        assert_eq!(*store.get_state(), 3);
+
+        assert!(matches!(store.dispatch_json(r#"{"Subtract": 3}"#), Err(Error::Json(_))));
+        assert_eq!(*store.get_state(), 3);
+    }
+
+    #[test]
+    fn an_edit_notifies_once_however_much_it_changes() {
+        let mut store: Store<(i32, i32), Counter> = Store::new((0, 0));
+        store.register_reducer(Box::new(|state, _| *state));
+        let notified = Arc::new(Mutex::new(vec![]));
+        let observed = notified.clone();
+        store.subscribe(move |state| observed.lock().unwrap().push(*state));
+
+        store.edit(|state| {
+            state.0 = 1;
+            state.1 = 2;
+        });
+        assert_eq!(*notified.lock().unwrap(), vec![(1, 2)]);
+
+        // a failed edit is dropped, with nobody notified
+        assert_eq!(store.try_edit(|state| {
+            state.0 = 3;
+            Err("refused")
+        }), Err("refused"));
+        assert_eq!(*store.get_state(), (1, 2));
+        assert_eq!(notified.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn suspended_observers_hear_once_on_resuming() {
+        let mut store = counting_store();
+        let notified = Arc::new(Mutex::new(vec![]));
+        let observed = notified.clone();
+        store.observe_with_action(|count| *count, move |action, count| observed.lock().unwrap().push((action.clone(), *count)));
+
+        store.suspend_observers();
+        (1..=3).for_each(|n| store.dispatch(Counter::Add(n)).unwrap());
+        assert!(notified.lock().unwrap().is_empty());
+
+        store.resume_observers();
+        assert_eq!(*notified.lock().unwrap(), vec![(Counter::Add(3), 6)]);
+
+        // with nothing dispatched in between, there's nothing to hear
+        store.suspend_observers();
+        store.resume_observers();
+        assert_eq!(notified.lock().unwrap().len(), 1);
+    }
+
+    // On tokio's paused clock, moved on by hand alongside the replay. A delay from a paused clock
+    // ends a millisecond late, as deadlines are rounded up to the next, so each check moves it to
+    // just short of the interval and then just past it.
+    #[cfg(feature = "futures")]
+    #[tokio::test]
+    async fn replays_are_paced_by_the_interval_and_can_be_paused() {
+        let mut store = counting_store();
+        let counts = Arc::new(Mutex::new(vec![]));
+        let recorded = counts.clone();
+        store.subscribe(move |count| recorded.lock().unwrap().push(*count));
+        let interval = Duration::from_millis(100);
+        let millis = Duration::from_millis;
+
+        let controls = ReplayControls::new();
+        let actions = [Counter::Add(1), Counter::Add(2), Counter::Add(3)];
+        let replay = store.replay_timed(&actions, interval, &controls);
+
+        let steps = async {
+            let advance = |by: Duration| async move {
+                time::advance(by).await;
+                tokio::task::yield_now().await;
+            };
+            let counts = || counts.lock().unwrap().clone();
+
+            // the first straight away
+            tokio::task::yield_now().await;
+            assert_eq!(counts(), vec![1]);
+
+            // nothing while paused, however long for
+            controls.pause();
+            advance(interval + millis(1)).await;
+            advance(interval * 3).await;
+            assert_eq!(counts(), vec![1]);
+
+            // the pause is checked every interval
+            controls.resume();
+            advance(interval + millis(1)).await;
+            assert_eq!(counts(), vec![1, 3]);
+
+            advance(interval - millis(1)).await;
+            assert_eq!(counts(), vec![1, 3]);
+            advance(millis(2)).await;
+            assert_eq!(counts(), vec![1, 3, 6]);
+        };
+
+        time::pause();
+        let (replayed, ()) = futures::join!(replay, steps);
+        replayed.unwrap();
+    }
+
+    #[test]
+    fn unsubscribed_observers_hear_nothing_more() {
+        let mut store = counting_store();
+        let notified = Arc::new(Mutex::new(vec![]));
+        let observer = |name: &'static str| {
+            let notified = notified.clone();
+            move |count: &i32| notified.lock().unwrap().push((name, *count))
+        };
+
+        let first = store.observe(|count| *count, observer("first"));
+        store.observe(|count| *count, observer("second"));
+        store.unsubscribe(first.clone());
+        store.dispatch(Counter::Add(1)).unwrap();
+        assert_eq!(*notified.lock().unwrap(), vec![("second", 1)]);
+
+        // twice does nothing
+        store.unsubscribe(first);
+        store.dispatch(Counter::Add(1)).unwrap();
+        assert_eq!(notified.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn an_observer_can_unsubscribe_itself() {
+        let mut store = counting_store();
+        let notified = Arc::new(Mutex::new(vec![]));
+        let observed = notified.clone();
+        let own = Arc::new(Mutex::new(None::<SubscriptionHandle>));
+        let cancelling = own.clone();
+        let subscription = store.observe(|count| *count, move |count| {
+            observed.lock().unwrap().push(*count);
+            if *count >= 2 {
+                cancelling.lock().unwrap().as_ref().unwrap().cancel();
+            }
+        });
+        *own.lock().unwrap() = Some(subscription.clone());
+
+        (1..=3).for_each(|n| store.dispatch(Counter::Add(n)).unwrap());
+
+        assert_eq!(*notified.lock().unwrap(), vec![1, 3]);
+        assert!(subscription.is_cancelled());
+    }
+
+
+    #[test]
+    fn observers_can_select_values_of_any_type() {
+        let mut store = counting_store();
+        let labels = Arc::new(Mutex::new(vec![]));
+        let labelled = labels.clone();
+        let evens = Arc::new(Mutex::new(vec![]));
+        let evened = evens.clone();
+
+        store.observe(|count| format!("count {}", count), move |label: &String| labelled.lock().unwrap().push(label.clone()));
+        store.observe(|count| Some(count % 2 == 0).filter(|_| *count > 1), move |even: &Option<bool>| evened.lock().unwrap().push(*even));
+
+        store.dispatch(Counter::Add(1)).unwrap();
+        store.dispatch(Counter::Add(1)).unwrap();
+
+        assert_eq!(*labels.lock().unwrap(), vec!["count 1", "count 2"]);
+        assert_eq!(*evens.lock().unwrap(), vec![None, Some(true)]);
+    }
+
+
+    #[test]
+    fn distinct_observers_hear_only_of_changes() {
+        let mut store = counting_store();
+        let notified = Arc::new(Mutex::new(vec![]));
+        let observed = notified.clone();
+        store.observe_distinct(|count| *count > 0, move |positive| observed.lock().unwrap().push(*positive));
+
+        // the first always notifies, the rest leave the selection as it was
+        store.dispatch(Counter::Ping).unwrap();
+        store.dispatch(Counter::Pong).unwrap();
+        assert_eq!(*notified.lock().unwrap(), vec![false]);
+
+        store.dispatch(Counter::Add(1)).unwrap();
+        store.dispatch(Counter::Add(1)).unwrap();
+        assert_eq!(*notified.lock().unwrap(), vec![false, true]);
+    }
+
+
+    #[test]
+    fn observers_hear_what_the_selection_changed_from() {
+        let mut store = counting_store();
+        let changes = Arc::new(Mutex::new(vec![]));
+        let observer = |name: &'static str| {
+            let changes = changes.clone();
+            move |prev: Option<&i32>, count: &i32| changes.lock().unwrap().push((name, prev.cloned(), *count))
+        };
+
+        store.observe_with_prev(|count| *count, observer("count"));
+        store.dispatch(Counter::Add(1)).unwrap();
+        store.observe_with_prev(|count| count * 10, observer("tens"));
+        store.dispatch(Counter::Add(2)).unwrap();
+        store.dispatch(Counter::Add(3)).unwrap();
+
+        assert_eq!(*changes.lock().unwrap(), vec![
+            ("count", None, 1),
+            ("count", Some(1), 3),
+            ("tens", None, 30),
+            ("count", Some(3), 6),
+            ("tens", Some(30), 60),
+        ]);
+    }
+
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum NoteAction {
+        Add(i32, String),
+        ChangeText(i32, String),
+        Remove(i32),
+    }
+
+    fn notes_store() -> Store<Vec<(i32, String)>, NoteAction> {
+        let mut store = Store::new(vec![]);
+        store.register_reducer(Box::new(|notes: &Vec<(i32, String)>, action| {
+            let mut notes = notes.clone();
+            match action {
+                NoteAction::Add(id, text) => notes.push((*id, text.clone())),
+                NoteAction::ChangeText(id, text) => notes.iter_mut().filter(|note| note.0 == *id).for_each(|note| note.1 = text.clone()),
+                NoteAction::Remove(id) => notes.retain(|note| note.0 != *id),
+            }
+            notes
+        }));
+        store
+    }
+
+    #[test]
+    fn middlewares_can_change_drop_and_follow_up_actions() {
+        type Notes = Vec<(i32, String)>;
+        let mut store = notes_store();
+        store.apply_middleware(|action: DispatchedAction<NoteAction>, next: &mut Next<'_, Notes, NoteAction>| {
+            next.pass(action.map(|action| match action {
+                NoteAction::ChangeText(id, text) => NoteAction::ChangeText(id, text.to_uppercase()),
+                action => action,
+            }))
+        });
+        store.apply_middleware(|action: DispatchedAction<NoteAction>, next: &mut Next<'_, Notes, NoteAction>| {
+            if let NoteAction::Remove(_) = action.action {
+                return;
+            }
+            next.pass(action)
+        });
+        store.apply_middleware(|action: DispatchedAction<NoteAction>, next: &mut Next<'_, Notes, NoteAction>| {
+            let added = match &action.action {
+                NoteAction::Add(id, _) => Some(*id),
+                _ => None,
+            };
+            next.pass(action);
+            if let Some(id) = added {
+                next.dispatch(NoteAction::ChangeText(id, format!("note {}", id)));
+            }
+        });
+
+        store.dispatch(NoteAction::Add(1, "first".to_string())).unwrap();
+        assert_eq!(*store.get_state(), vec![(1, "NOTE 1".to_string())]);
+
+        store.dispatch(NoteAction::ChangeText(1, "changed".to_string())).unwrap();
+        store.dispatch(NoteAction::Remove(1)).unwrap();
+        assert_eq!(*store.get_state(), vec![(1, "CHANGED".to_string())]);
+    }
+
+
+    #[derive(Debug, Clone, PartialEq)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    struct Task {
+        id: i32,
+    }
+
+    impl crate::Identifiable for Task {
+        type Id = i32;
+
+        fn get_id(&self) -> i32 {
+            self.id
+        }
+    }
+
+    #[test]
+    fn thunks_dispatch_depending_on_the_state_so_far() {
+        use crate::{entity_reducer, Collection, EntityAction};
+
+        let mut store = Store::new(Collection::new());
+        store.register_reducer(Box::new(entity_reducer));
+        let seen = Arc::new(Mutex::new(vec![]));
+        let observed = seen.clone();
+        store.subscribe(move |tasks: &Collection<Task>| observed.lock().unwrap().push(tasks.len()));
+
+        // adds up to a limit of three
+        let add_within_limit = |id| move |ctx: &mut DispatchCtx<'_, Collection<Task>, EntityAction<Task>>| {
+            let total = Collection::select_total(|tasks: &Collection<Task>| tasks.clone())(&ctx.get_state());
+            if total < 3 {
+                ctx.dispatch(EntityAction::AddEntity(Task { id })).unwrap();
+            }
+        };
+        (1..=4).for_each(|id| store.dispatch_thunk(add_within_limit(id)));
+        assert_eq!(store.get_state().len(), 3);
+
+        // several dispatches, each seen through as a whole
+        store.dispatch_thunk(|ctx| {
+            ctx.dispatch(EntityAction::RemoveEntity(1)).unwrap();
+            ctx.dispatch(EntityAction::RemoveEntity(2)).unwrap();
+            assert_eq!(ctx.get_state().len(), 1);
+            ctx.dispatch(EntityAction::AddEntity(Task { id: 5 })).unwrap();
+        });
+        assert_eq!(*seen.lock().unwrap(), vec![1, 2, 3, 2, 1, 2]);
+        assert_eq!(store.get_state().ids().iter().cloned().collect::<Vec<_>>(), vec![3, 5]);
+    }
+
+
+    #[test]
+    fn effects_translate_actions_into_others() {
+        let mut store = notes_store();
+        // emptying a note removes it, once the emptying has been seen
+        store.register_effect(|action: &DispatchedAction<NoteAction>, _: &Vec<(i32, String)>| match &action.action {
+            NoteAction::ChangeText(id, text) if text.is_empty() => Some(NoteAction::Remove(*id)),
+            _ => None,
+        });
+        let seen = Arc::new(Mutex::new(vec![]));
+        let observed = seen.clone();
+        store.subscribe(move |notes: &Vec<(i32, String)>| observed.lock().unwrap().push(notes.len()));
+
+        store.dispatch(NoteAction::Add(1, "first".to_string())).unwrap();
+        store.dispatch(NoteAction::Add(2, "second".to_string())).unwrap();
+        store.dispatch(NoteAction::ChangeText(1, String::new())).unwrap();
+
+        assert_eq!(*store.get_state(), vec![(2, "second".to_string())]);
+        assert_eq!(*seen.lock().unwrap(), vec![1, 2, 2, 1]);
+    }
+
+    #[test]
+    fn effects_triggering_each_other_fail_at_the_depth_limit() {
+        let mut store = counting_store();
+        store.limit_effect_depth(5);
+        store.register_effect(|action, _| match action.action {
+            Counter::Ping => vec![Counter::Add(1), Counter::Pong],
+            Counter::Pong => vec![Counter::Ping],
+            _ => vec![],
+        });
+
+        match store.dispatch(Counter::Ping) {
+            Err(DispatchError::EffectDepth { depth, limit }) => assert_eq!((depth, limit), (6, 5)),
+            result => panic!("expected the cycle to be caught, got {:?}", result),
+        }
+        // Ping, Pong and Ping again before the limit, each Ping adding one
+        assert_eq!(*store.get_state(), 3);
+
+        store.dispatch(Counter::Add(1)).unwrap();
+        assert_eq!(*store.get_state(), 4);
+    }
+
+
+    #[test]
+    fn combined_slices_reduce_the_same_actions_independently() {
+        let mut store = Store::<(i32, usize), Counter, CounterError>::with_error((0, 0));
+        store.register_fallible_reducer(combine_reducers(vec![
+            slice_reducer(|state: &(i32, usize)| state.0, |state, count| state.0 = count, |count, action| match action {
+                Counter::Add(n) => count + n,
+                _ => *count,
+            }),
+            try_slice_reducer(|state: &(i32, usize)| state.1, |state, seen| state.1 = seen, |seen, action| match action {
+                Counter::Add(n) if *n > 100 => Err(CounterError::TooBig),
+                _ => Ok(seen + 1),
+            }),
+        ]));
+
+        store.dispatch(Counter::Add(2)).unwrap();
+        store.dispatch(Counter::Ping).unwrap();
+        assert_eq!(*store.get_state(), (2, 2));
+
+        // the count isn't left added to when the other slice fails
+        assert!(matches!(store.dispatch(Counter::Add(101)), Err(DispatchError::Reducer { error: CounterError::TooBig, .. })));
+        assert_eq!(*store.get_state(), (2, 2));
+    }
+
+
+    #[test]
+    fn reducers_can_be_replaced_in_place_and_restored() {
+        let mut store: Store<i32, Counter> = Store::new(0);
+        let adding = |count: &i32, action: &Counter| match action {
+            Counter::Add(n) => count + n,
+            _ => *count,
+        };
+        let handle = store.register_reducer(Box::new(adding));
+        // after the adding, so doubles what it's added
+        store.register_reducer(Box::new(|count, _| count * 2));
+
+        store.dispatch(Counter::Add(1)).unwrap();
+        assert_eq!(*store.get_state(), 2);
+
+        assert!(store.replace_reducer(handle, Box::new(|count, _| *count)));
+        store.dispatch(Counter::Add(1)).unwrap();
+        assert_eq!(*store.get_state(), 4);
+
+        assert!(store.replace_reducer(handle, Box::new(adding)));
+        store.dispatch(Counter::Add(1)).unwrap();
+        assert_eq!(*store.get_state(), 10);
+        assert_eq!(store.reducer_handles().first(), Some(&handle));
+
+        assert!(store.unregister_reducer(handle));
+        assert!(!store.unregister_reducer(handle));
+        assert!(!store.replace_reducer(handle, Box::new(adding)));
+        store.dispatch(Counter::Add(1)).unwrap();
+        assert_eq!(*store.get_state(), 20);
+    }
+
+
+    #[test]
+    fn batches_notify_once_with_what_single_dispatches_reach() {
+        let actions = vec![Counter::Add(1), Counter::Ping, Counter::Add(2), Counter::Add(3)];
+        let mut single = counting_store();
+        actions.iter().cloned().for_each(|action| single.dispatch(action).unwrap());
+
+        let mut store = counting_store();
+        let notified = Arc::new(Mutex::new(vec![]));
+        let observed = notified.clone();
+        store.subscribe(move |count| observed.lock().unwrap().push(*count));
+
+        store.dispatch_all(actions.clone()).unwrap();
+        assert_eq!(*notified.lock().unwrap(), vec![6]);
+        assert_eq!(store.get_state(), single.get_state());
+
+        // nested, only the outermost notifies
+        store.batch(|store| {
+            store.dispatch(Counter::Add(1)).unwrap();
+            store.batch(|store| store.dispatch_all(actions).unwrap());
+        });
+        assert_eq!(*notified.lock().unwrap(), vec![6, 13]);
+    }
+
+
+    #[test]
+    fn observers_dispatch_through_a_dispatcher_once_notified() {
+        let mut store = counting_store();
+        let dispatcher = store.dispatcher();
+        // tops a count of 5 or more up to 10
+        store.observe(|count| *count, move |count| if (5..10).contains(count) {
+            dispatcher.dispatch(Counter::Add(10 - count));
+        });
+
+        store.dispatch(Counter::Add(3)).unwrap();
+        assert_eq!(*store.get_state(), 3);
+        store.dispatch(Counter::Add(3)).unwrap();
+        assert_eq!(*store.get_state(), 10);
+
+        // observers dispatching on every notification are caught like any other cycle
+        let dispatcher = store.dispatcher();
+        store.observe(|count| *count, move |_| dispatcher.dispatch(Counter::Add(1)));
+        assert!(matches!(store.dispatch(Counter::Add(1)), Err(DispatchError::EffectDepth { .. })));
+    }
+
+
+    #[test]
+    fn channels_feed_another_thread_until_the_receiver_goes() {
+        let mut store = counting_store();
+        let counts = store.observe_channel(|count| *count);
+        let evens = store.observe_channel_distinct(|count| count % 2 == 0);
+        let consumer = std::thread::spawn(move || (counts.iter().take(4).collect::<Vec<_>>(), evens.iter().take(2).collect::<Vec<_>>()));
+
+        [1, 0, 1, 2].iter().for_each(|n| store.dispatch(Counter::Add(*n)).unwrap());
+
+        // both receivers have gone with the consumer
+        assert_eq!(consumer.join().unwrap(), (vec![1, 1, 2, 4], vec![false, true]));
+        assert_eq!(store.observers.len(), 2);
+
+        // their observers find out on the next send, and are gone once it's done
+        store.dispatch(Counter::Add(1)).unwrap();
+        assert!(store.observers.is_empty());
+        store.dispatch(Counter::Add(1)).unwrap();
+        assert_eq!(*store.get_state(), 6);
+    }
+
+
+    #[test]
+    fn dispatchers_queue_from_other_threads_in_order() {
+        use crate::{entity_reducer, Collection, EntityAction};
+
+        let mut store = Store::new(Collection::new());
+        store.register_reducer(Box::new(entity_reducer));
+
+        let threads: Vec<_> = (0..2).map(|thread| {
+            let dispatcher = store.dispatcher();
+            std::thread::spawn(move || for n in 0..100 {
+                dispatcher.dispatch(EntityAction::AddEntity(Task { id: thread * 100 + n }));
+            })
+        }).collect();
+        threads.into_iter().for_each(|thread| thread.join().unwrap());
+        store.process_pending().unwrap();
+
+        let ids: Vec<i32> = store.get_state().ids().iter().cloned().collect();
+        assert_eq!(ids.len(), 200);
+        // each thread's in the order it queued them
+        for thread in 0..2 {
+            let queued: Vec<_> = ids.iter().filter(|id| **id / 100 == thread).cloned().collect();
+            assert_eq!(queued, (thread * 100..thread * 100 + 100).collect::<Vec<_>>());
+        }
+
+        // queued actions follow the action of the next dispatch
+        let dispatcher = store.dispatcher();
+        store.dispatch(EntityAction::RemoveAll).unwrap();
+        dispatcher.dispatch(EntityAction::AddEntity(Task { id: 1 }));
+        dispatcher.dispatch(EntityAction::AddEntity(Task { id: 2 }));
+        store.dispatch(EntityAction::AddEntity(Task { id: 3 })).unwrap();
+        assert_eq!(store.get_state().ids().iter().cloned().collect::<Vec<_>>(), vec![3, 1, 2]);
+    }
+
+    // on a runtime of its own, as tokio's test runtime has no handle to give the effect
+    #[cfg(feature = "futures")]
+    #[test]
+    fn async_effects_dispatch_once_done_or_go_with_the_runtime() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let held = Arc::new(());
+        let mut store = counting_store();
+        let holding = held.clone();
+        store.register_async_effect(runtime.handle().clone(), move |action, _| {
+            let holding = Some(holding.clone()).filter(|_| action.action == Counter::Pong);
+            async move {
+                match action.action {
+                    Counter::Ping => {
+                        time::delay_for(std::time::Duration::from_millis(10)).await;
+                        Some(Counter::Add(5))
+                    },
+                    // never done while the test runs
+                    Counter::Pong => {
+                        time::delay_for(std::time::Duration::from_secs(60)).await;
+                        drop(holding);
+                        Some(Counter::Add(1))
+                    },
+                    _ => None,
+                }
+            }
+        });
+
+        store.dispatch(Counter::Ping).unwrap();
+        assert_eq!(*store.get_state(), 0);
+        for _ in 0..100 {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            store.process_pending().unwrap();
+            if *store.get_state() != 0 {
+                break;
+            }
+        }
+        assert_eq!(*store.get_state(), 5);
+
+        // the effect and the Pong's future, still running, each hold a clone
+        store.dispatch(Counter::Pong).unwrap();
+        assert_eq!(Arc::strong_count(&held), 3);
+        drop(runtime);
+        assert_eq!(Arc::strong_count(&held), 2);
+        drop(store);
+        assert_eq!(Arc::strong_count(&held), 1);
+    }
+
+
+    #[test]
+    fn travelling_back_and_dispatching_drops_the_future() {
+        let mut store = counting_store();
+        store.enable_history(10);
+        let notified = Arc::new(Mutex::new(vec![]));
+        let observed = notified.clone();
+        store.subscribe(move |count| observed.lock().unwrap().push(*count));
+
+        (1..=5).for_each(|n| store.dispatch(Counter::Add(n)).unwrap());
+        assert!(store.jump_to(3));
+        assert_eq!(*store.get_state(), 6);
+        assert!(store.step_back());
+        assert!(store.step_forward());
+        assert_eq!((*store.get_state(), store.history_position()), (6, 3));
+        assert_eq!(*notified.lock().unwrap(), vec![1, 3, 6, 10, 15, 6, 3, 6]);
+
+        store.dispatch(Counter::Add(100)).unwrap();
+        let history: Vec<_> = store.history().iter().map(|entry| (entry.action.as_ref().map(|a| a.action.clone()), *entry.state)).collect();
+        assert_eq!(history, vec![
+            (None, 0),
+            (Some(Counter::Add(1)), 1),
+            (Some(Counter::Add(2)), 3),
+            (Some(Counter::Add(3)), 6),
+            (Some(Counter::Add(100)), 106),
+        ]);
+        assert!(!store.step_forward());
+        assert!(!store.jump_to(5));
+    }
+
+
+    // a second later every time it's asked
+    struct TickingClock(std::cell::Cell<u64>);
+
+    impl Clock for TickingClock {
+        fn now(&self) -> std::time::SystemTime {
+            self.0.set(self.0.get() + 1);
+            std::time::UNIX_EPOCH + std::time::Duration::from_secs(self.0.get())
+        }
+    }
+
+    #[test]
+    fn every_dispatch_gets_the_next_sequence_number_and_the_clocks_time() {
+        let mut store = counting_store();
+        store.set_clock(TickingClock(Default::default()));
+        store.register_effect(|action, _| match action.action {
+            Counter::Ping => Some(Counter::Add(1)),
+            _ => None,
+        });
+        let dispatcher = store.dispatcher();
+        store.observe(|count| *count, move |count| if *count == 1 {
+            dispatcher.dispatch(Counter::Pong);
+        });
+
+        let seen = Arc::new(Mutex::new(vec![]));
+        let recorded = seen.clone();
+        store.apply_middleware(move |action: DispatchedAction<Counter>, next: &mut Next<'_, i32, Counter>| {
+            let secs = action.timestamp.duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+            recorded.lock().unwrap().push((action.action.clone(), action.sequence, secs, action.correlation_id.clone()));
+            next.pass(action);
+        });
+
+        // queued from elsewhere, so dispatched once the Ping's done, before the Pong the Add(1) leads to
+        store.dispatcher().dispatch(Counter::Add(10));
+        store.dispatch_with_meta(Counter::Ping, DispatchMeta { correlation_id: Some("ping".to_string()) }).unwrap();
+
+        assert_eq!(*seen.lock().unwrap(), vec![
+            (Counter::Ping, 1, 1, Some("ping".to_string())),
+            (Counter::Add(1), 2, 2, None),
+            (Counter::Add(10), 3, 3, None),
+            (Counter::Pong, 4, 4, None),
+        ]);
+    }
+
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn a_snapshot_hydrates_a_fresh_store() {
+        use crate::{entity_reducer, Collection, EntityAction};
+
+        let tasks = || {
+            let mut store = Store::new(Collection::new());
+            store.register_reducer(Box::new(entity_reducer));
+            store
+        };
+        let mut store = tasks();
+        store.dispatch(EntityAction::AddMany(vec![Task { id: 3 }, Task { id: 1 }, Task { id: 2 }])).unwrap();
+        store.dispatch(EntityAction::RemoveEntity(1)).unwrap();
+        store.dispatch(EntityAction::MoveEntity(2, 0)).unwrap();
+
+        let mut hydrated = tasks();
+        hydrated.hydrate(&store.snapshot().unwrap()).unwrap();
+        let select_ids = || Collection::select_ids(|tasks: &Collection<Task>| tasks.clone());
+        assert_eq!(hydrated.select(select_ids()), vec![2, 3]);
+        assert_eq!(hydrated.select(select_ids()), store.select(select_ids()));
+
+        assert!(matches!(hydrated.hydrate(r#"{"ids": [2, 3"#), Err(Error::Json(_))));
+        assert_eq!(hydrated.get_state(), store.get_state());
+    }
+
+
+    // counts its clones in the counter it shares with the others
+    #[derive(Debug)]
+    struct Counted {
+        count: i32,
+        clones: Arc<Mutex<usize>>,
+    }
+
+    impl Clone for Counted {
+        fn clone(&self) -> Self {
+            *self.clones.lock().unwrap() += 1;
+            Counted { count: self.count, clones: self.clones.clone() }
+        }
+    }
+
+    #[test]
+    fn dispatching_to_several_observers_clones_nothing() {
+        let clones = Arc::new(Mutex::new(0));
+        let mut store: Store<Counted, Counter> = Store::new(Counted { count: 0, clones: clones.clone() });
+        store.register_reducer(Box::new(|state: &Counted, action| match action {
+            Counter::Add(n) => Counted { count: state.count + n, clones: state.clones.clone() },
+            _ => Counted { count: state.count, clones: state.clones.clone() },
+        }));
+        let seen = Arc::new(Mutex::new(vec![]));
+        for _ in 0..3 {
+            let observed = seen.clone();
+            store.observe(|state| state.count, move |count| observed.lock().unwrap().push(*count));
+        }
+        store.subscribe(|_| {});
+
+        (1..=3).for_each(|n| store.dispatch(Counter::Add(n)).unwrap());
+
+        assert_eq!(seen.lock().unwrap().len(), 9);
+        assert_eq!(*clones.lock().unwrap(), 0);
+    }
+
+
+    #[test]
+    fn a_refused_action_leaves_the_state_and_observers_alone() {
+        use crate::{entity_reducer, Collection, EntityAction};
+
+        let mut store: Store<Collection<Task>, EntityAction<Task>> = Store::new(Collection::new());
+        store.register_reducer(Box::new(entity_reducer));
+        let checking = store.register_fallible_reducer(Box::new(|tasks: &Collection<Task>, action| match action {
+            EntityAction::ReplaceEntity(task) if !tasks.contains(&task.id) => Err(Error::NotFound(task.id)),
+            _ => Ok(tasks.clone()),
+        }));
+        let notified = Arc::new(Mutex::new(0));
+        let counted = notified.clone();
+        store.subscribe(move |_| *counted.lock().unwrap() += 1);
+
+        store.dispatch(EntityAction::AddEntity(Task { id: 1 })).unwrap();
+        let before = store.get_state();
+
+        match store.dispatch(EntityAction::ReplaceEntity(Task { id: 2 })) {
+            Err(DispatchError::Reducer { reducer, error: Error::NotFound(2) }) => assert_eq!(reducer, checking),
+            result => panic!("expected the checking reducer to refuse, got {:?}", result),
+        }
+        assert!(Arc::ptr_eq(&before, &store.get_state()));
+        assert_eq!((*notified.lock().unwrap(), store.version()), (1, 1));
+
+        store.dispatch(EntityAction::AddEntity(Task { id: 2 })).unwrap();
+        assert_eq!(store.get_state().len(), 2);
+        assert_eq!(*notified.lock().unwrap(), 2);
+    }
+
+
+    #[test]
+    fn snapshots_outlive_later_dispatches_without_cloning() {
+        let clones = Arc::new(Mutex::new(0));
+        let mut store: Store<Counted, Counter> = Store::new(Counted { count: 0, clones: clones.clone() });
+        store.register_reducer(Box::new(|state: &Counted, action| match action {
+            Counter::Add(n) => Counted { count: state.count + n, clones: state.clones.clone() },
+            _ => Counted { count: state.count, clones: state.clones.clone() },
+        }));
+        store.dispatch(Counter::Add(1)).unwrap();
+
+        let snapshot = store.get_state();
+        store.dispatch(Counter::Add(2)).unwrap();
+        store.dispatch(Counter::Add(3)).unwrap();
+
+        assert_eq!(snapshot.count, 1);
+        assert_eq!(store.get_state().count, 6);
+        assert_eq!(store.select(Box::new(|state: &Counted| state.count)), 6);
+        assert_eq!(*clones.lock().unwrap(), 0);
+    }
+
+
+    #[test]
+    fn subscribers_hear_once_per_dispatch_or_batch_until_unsubscribed() {
+        let mut store = counting_store();
+        let notified = Arc::new(Mutex::new(vec![]));
+        let observed = notified.clone();
+        let subscription = store.subscribe(move |count| observed.lock().unwrap().push(*count));
+
+        store.dispatch(Counter::Add(1)).unwrap();
+        store.dispatch(Counter::Add(2)).unwrap();
+        store.dispatch_all(vec![Counter::Add(3), Counter::Add(4)]).unwrap();
+        assert_eq!(*notified.lock().unwrap(), vec![1, 3, 10]);
+
+        store.unsubscribe(subscription);
+        store.dispatch(Counter::Add(5)).unwrap();
+        assert_eq!(notified.lock().unwrap().len(), 3);
+    }
+
+
+    #[test]
+    fn a_panicking_observer_doesnt_stop_the_others() {
+        let mut store = counting_store();
+        let errors = Arc::new(Mutex::new(vec![]));
+        let reported = errors.clone();
+        store.on_observer_error(move |subscription, message| reported.lock().unwrap().push((subscription.id(), message.to_string())));
+        store.limit_observer_panics(2);
+
+        let seen = Arc::new(Mutex::new(vec![]));
+        let first = seen.clone();
+        let third = seen.clone();
+        store.observe(|count| *count, move |count| first.lock().unwrap().push(("first", *count)));
+        let panicking = store.observe(|count| *count, |_| panic!("unlucky"));
+        store.observe(|count| *count, move |count| third.lock().unwrap().push(("third", *count)));
+
+        store.dispatch(Counter::Add(1)).unwrap();
+        assert_eq!(*seen.lock().unwrap(), vec![("first", 1), ("third", 1)]);
+        assert_eq!(*errors.lock().unwrap(), vec![(panicking.id(), "unlucky".to_string())]);
+        assert_eq!(*store.get_state(), 1);
+
+        // unsubscribed on panicking a second time in a row
+        store.dispatch(Counter::Add(1)).unwrap();
+        store.dispatch(Counter::Add(1)).unwrap();
+        assert!(panicking.is_cancelled());
+        assert_eq!(errors.lock().unwrap().len(), 2);
+        assert_eq!(seen.lock().unwrap().len(), 6);
+    }
+
+
+    #[test]
+    fn the_version_only_counts_real_changes() {
+        let mut store = counting_store();
+        store.version_by_equality();
+        let versions = Arc::new(Mutex::new(vec![]));
+        let observed = versions.clone();
+        store.observe_with_version(|count| *count, move |version, count| observed.lock().unwrap().push((version, *count)));
+
+        store.dispatch(Counter::Ping).unwrap();
+        assert_eq!(store.version(), 0);
+        store.dispatch(Counter::Add(2)).unwrap();
+        assert_eq!(store.version(), 1);
+
+        // netting out to no change
+        store.dispatch_all(vec![Counter::Add(1), Counter::Add(-1)]).unwrap();
+        assert_eq!(store.version(), 1);
+
+        assert_eq!(*versions.lock().unwrap(), vec![(0, 0), (1, 2), (1, 2)]);
+    }
+
+
+    #[test]
+    fn deferred_actions_wait_for_process() {
+        let mut store = counting_store();
+        let dispatcher = store.dispatcher();
+        store.observe(|count| *count, move |count| if *count == 6 {
+            dispatcher.defer(Counter::Add(10));
+        });
+        let notified = Arc::new(Mutex::new(vec![]));
+        let observed = notified.clone();
+        store.subscribe(move |count| observed.lock().unwrap().push(*count));
+
+        (1..=3).for_each(|n| store.dispatch_deferred(Counter::Add(n)));
+        assert_eq!(*store.get_state(), 0);
+
+        // the observer's deferred action is processed too, in a round of its own
+        assert_eq!(store.process(), 4);
+        assert_eq!(*store.get_state(), 16);
+        assert_eq!(*notified.lock().unwrap(), vec![6, 16]);
+
+        assert_eq!(store.process(), 0);
+    }
+
+
+    #[test]
+    fn process_counts_the_deferred_actions_the_reducers_took() {
+        let mut store = Store::<i32, Counter, CounterError>::with_error(0);
+        store.register_fallible_reducer(Box::new(|count, action| match action {
+            Counter::Add(n) if *n > 100 => Err(CounterError::TooBig),
+            Counter::Add(n) => Ok(count + n),
+            _ => Ok(*count),
+        }));
+        // a Ping's follow-up is always refused
+        store.register_effect(|action, _| match action.action {
+            Counter::Ping => Some(Counter::Add(101)),
+            _ => None,
+        });
+        store.skip_noops();
+        store.retain_idempotency_keys(8);
+
+        let deferred = [Counter::Ping, Counter::Add(0), Counter::Add(3), Counter::Add(3), Counter::Add(101)];
+        deferred.iter().cloned().for_each(|action| store.dispatch_deferred(action));
+
+        // the Ping and the first Add(3); the rest are a no-op, a repeat and refused
+        assert_eq!(store.process(), 2);
+        assert_eq!(*store.get_state(), 3);
+    }
+}
@@ -0,0 +1,68 @@
+// Helpers for regression tests around dispatch
+
+use std::fmt::Debug;
+
+use crate::store::{Store};
+
+// Dispatches action and hands expect the new state alongside a copy of the old one. expect makes
+// its targeted assertions and applies the intended change to the copy; anything still differing
+// afterwards changed unexpectedly and fails the assertion.
+pub fn assert_only_changed<State, Action, E>(store: &mut Store<State, Action, E>, action: Action, expect: impl FnOnce(&mut State, &State))
+    where State: Clone + PartialEq + Debug, Action: Clone, E: Debug {
+
+    let mut expected = State::clone(&store.get_state());
+    store.dispatch(action).expect("dispatch failed");
+
+    let new = store.get_state();
+    expect(&mut expected, &new);
+
+    assert_eq!(expected, *new, "dispatch changed more state than expected");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Collection, Identifiable};
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Todo {
+        id: i32,
+        done: bool,
+    }
+
+    impl Identifiable for Todo {
+        type Id = i32;
+
+        fn get_id(&self) -> i32 {
+            self.id
+        }
+    }
+
+    fn done(todos: &Collection<Todo>, id: i32) -> Collection<Todo> {
+        todos.clone().map_one(&id, |todo| todo.done = true)
+    }
+
+    // marks the todo with the id dispatched done, though marking 2 done marks 3 done too
+    fn todo_store() -> Store<Collection<Todo>, i32> {
+        let mut store = Store::new((1..=3).map(|id| Todo { id, done: false }).collect());
+        store.register_reducer(Box::new(|todos, &id| match id {
+            2 => done(&done(todos, 2), 3),
+            _ => done(todos, id),
+        }));
+        store
+    }
+
+    #[test]
+    fn marking_a_todo_done_changes_only_that_todo() {
+        assert_only_changed(&mut todo_store(), 1, |expected, new| {
+            assert!(new.get(&1).unwrap().done);
+            *expected = done(expected, 1);
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "dispatch changed more state than expected")]
+    fn anything_else_changing_fails() {
+        assert_only_changed(&mut todo_store(), 2, |expected, _| *expected = done(expected, 2));
+    }
+}
@@ -0,0 +1,179 @@
+use im::Vector;
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+pub enum UndoAction {
+    Undo,
+    Redo,
+    // forgets both what can be undone and redone
+    ClearHistory,
+}
+
+// Lets undoable tell the undo actions among an action type's own variants, e.g.
+// TodoAction::History(UndoAction)
+pub trait UndoableAction {
+    fn undo_action(&self) -> Option<UndoAction>;
+
+    // false for actions that shouldn't be undone on their own, e.g. selecting a todo. Their change
+    // is kept, becoming part of the entry before.
+    fn records_history(&self) -> bool {
+        true
+    }
+}
+
+// A state along with the states before it, to undo back to, and those undone, to redo. Most recent
+// last in both, which are shared between versions so the reducer doesn't copy them on every action.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Undoable<State: Clone> {
+    pub past: Vector<State>,
+    pub present: State,
+    pub future: Vector<State>,
+}
+
+impl<State: Clone> Undoable<State> {
+    pub fn new(present: State) -> Undoable<State> {
+        Undoable { past: Vector::new(), present, future: Vector::new() }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.past.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.future.is_empty()
+    }
+}
+
+// Wraps reducer to keep up to limit states to undo back to. Undoing with nothing to undo, or redoing
+// with nothing to redo, does nothing. Any other recorded action forgets what was undone, unless
+// reducer refuses it, which leaves everything as it was.
+pub fn undoable<State, Action, E>(reducer: impl Fn(&State, &Action) -> Result<State, E> + 'static, limit: usize) -> impl Fn(&Undoable<State>, &Action) -> Result<Undoable<State>, E>
+    where State: Clone, Action: UndoableAction {
+
+    move |undoable, action| {
+        let mut undoable = undoable.clone();
+
+        match action.undo_action() {
+            Some(UndoAction::Undo) => {
+                if let Some(previous) = undoable.past.pop_back() {
+                    let undone = std::mem::replace(&mut undoable.present, previous);
+                    undoable.future.push_back(undone);
+                }
+            },
+            Some(UndoAction::Redo) => {
+                if let Some(next) = undoable.future.pop_back() {
+                    let previous = std::mem::replace(&mut undoable.present, next);
+                    undoable.past.push_back(previous);
+                }
+            },
+            Some(UndoAction::ClearHistory) => {
+                undoable.past.clear();
+                undoable.future.clear();
+            },
+            None if action.records_history() => {
+                let present = reducer(&undoable.present, action)?;
+                undoable.past.push_back(std::mem::replace(&mut undoable.present, present));
+                if undoable.past.len() > limit {
+                    undoable.past.pop_front();
+                }
+                undoable.future.clear();
+            },
+            None => {
+                undoable.present = reducer(&undoable.present, action)?;
+            },
+        }
+
+        Ok(undoable)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug)]
+    enum Action {
+        Add(i32),
+        // changes the state without an undo entry of its own
+        Nudge,
+        History(UndoAction),
+    }
+
+    impl UndoableAction for Action {
+        fn undo_action(&self) -> Option<UndoAction> {
+            match self {
+                Action::History(undo) => Some(*undo),
+                _ => None,
+            }
+        }
+
+        fn records_history(&self) -> bool {
+            !matches!(self, Action::Nudge)
+        }
+    }
+
+    fn reducer() -> impl Fn(&Undoable<i32>, &Action) -> Result<Undoable<i32>, String> {
+        undoable(|count: &i32, action: &Action| match action {
+            Action::Add(n) if *n < 0 => Err(format!("can't add {}", n)),
+            Action::Add(n) => Ok(count + n),
+            Action::Nudge => Ok(count + 100),
+            Action::History(_) => Ok(*count),
+        }, 2)
+    }
+
+    fn run(actions: &[Action]) -> Result<Undoable<i32>, String> {
+        let reducer = reducer();
+        actions.iter().try_fold(Undoable::new(0), |state, action| reducer(&state, action))
+    }
+
+    #[test]
+    fn undoing_past_the_beginning_does_nothing() {
+        let state = run(&[Action::Add(1), Action::History(UndoAction::Undo), Action::History(UndoAction::Undo)]).unwrap();
+
+        assert_eq!(state.present, 0);
+        assert!(!state.can_undo());
+        assert!(state.can_redo());
+    }
+
+    #[test]
+    fn redo_brings_back_what_was_undone() {
+        let state = run(&[Action::Add(1), Action::Add(2), Action::History(UndoAction::Undo), Action::History(UndoAction::Redo)]).unwrap();
+
+        assert_eq!(state.present, 3);
+        assert!(!state.can_redo());
+    }
+
+    #[test]
+    fn a_new_action_forgets_what_was_undone() {
+        let state = run(&[Action::Add(1), Action::History(UndoAction::Undo), Action::Add(5)]).unwrap();
+
+        assert_eq!(state.present, 5);
+        assert!(!state.can_redo());
+    }
+
+    #[test]
+    fn only_the_last_limit_states_are_kept() {
+        let state = run(&[Action::Add(1), Action::Add(2), Action::Add(3)]).unwrap();
+
+        assert_eq!(state.past, Vector::from(vec![1, 3]));
+    }
+
+    #[test]
+    fn unrecorded_changes_join_the_entry_before() {
+        let state = run(&[Action::Add(1), Action::Nudge, Action::History(UndoAction::Undo)]).unwrap();
+
+        assert_eq!(state.present, 0);
+        assert_eq!(state.future, Vector::from(vec![101]));
+    }
+
+    #[test]
+    fn a_refused_action_leaves_everything_as_it_was() {
+        let reducer = reducer();
+        let state = run(&[Action::Add(1), Action::History(UndoAction::Undo)]).unwrap();
+
+        assert_eq!(reducer(&state, &Action::Add(-1)), Err(String::from("can't add -1")));
+        assert!(state.can_redo());
+    }
+}